@@ -25,5 +25,8 @@ fn main() {
         "pointer_sensitivity: {}",
         system_properties::pointer_sensitivity().unwrap()
     );
-    println!("screen_info: {:?}", system_properties::screen_info());
+    println!(
+        "screen_info: {:?}",
+        system_properties::screen_info().unwrap()
+    );
 }
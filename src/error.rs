@@ -1,117 +1,391 @@
 use std::any::Any;
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Duration;
 
 use thiserror::Error;
 use uiohook_sys as ffi;
 
-#[derive(Debug, Error)]
-#[error("
-    Trying to post invalid event type `{0}`, control events such as Enable and Disable cannot be posted.
-    Please use hook_start, hook_stop or similar APIs.
-")]
-pub struct PostEventError(pub String);
+use crate::hook::event::HookEvent;
+use crate::hook::global::HookId;
 
-#[cfg(target_os = "linux")]
+/// A panic caught while calling a hook, reported to the panic handler of the session,
+/// see [`HookSession::on_panic`].
+///
+/// [`HookSession::on_panic`]: crate::hook::session::HookSession::on_panic
 #[derive(Debug, Error)]
-pub enum HookError {
-    #[error("Failed to allocate memory.")]
-    OutOfMemory,
-    #[error("Failed to open X11 display.")]
-    XOpenDisplay,
-    #[error("Unable to locate XRecord extension.")]
-    XRecordNotFound,
-    #[error("Unable to allocate XRecord range.")]
-    XRecordAllocRange,
-    #[error("Unable to allocate XRecord context.")]
-    XRecordCreateContext,
-    #[error("Failed to enable XRecord context.")]
-    XRecordEnableContext,
-    #[error("Could not retrieve XRecord context.")]
-    XRecordGetContext,
-    #[error("Encountered unknown error.")]
-    Unknown(&'static str, Box<dyn Any + Send + 'static>),
+#[error("Hook {hook_id} panicked while handling {event:?}: {message}")]
+pub struct HookPanic {
+    /// The id of the hook that panicked.
+    pub hook_id: HookId,
+    /// The event the hook was handling when it panicked.
+    pub event: HookEvent,
+    /// The panic message, if the panic payload is a string.
+    pub message: String,
+    /// The panic payload, it can be used to continue the panic with [`std::panic::resume_unwind`].
+    pub payload: Box<dyn Any + Send + 'static>,
 }
 
-#[cfg(target_os = "linux")]
-impl From<u32> for HookError {
-    fn from(native_error: u32) -> Self {
-        match native_error {
-            ffi::UIOHOOK_ERROR_OUT_OF_MEMORY => HookError::OutOfMemory,
-            ffi::UIOHOOK_ERROR_X_OPEN_DISPLAY => HookError::XOpenDisplay,
-            ffi::UIOHOOK_ERROR_X_RECORD_NOT_FOUND => HookError::XRecordNotFound,
-            ffi::UIOHOOK_ERROR_X_RECORD_ALLOC_RANGE => HookError::XRecordAllocRange,
-            ffi::UIOHOOK_ERROR_X_RECORD_CREATE_CONTEXT => HookError::XRecordCreateContext,
-            ffi::UIOHOOK_ERROR_X_RECORD_ENABLE_CONTEXT => HookError::XRecordEnableContext,
-            ffi::UIOHOOK_ERROR_X_RECORD_GET_CONTEXT => HookError::XRecordGetContext,
-            _ => HookError::Unknown("unknown error code", Box::new(())),
+impl HookPanic {
+    pub(crate) fn new(hook_id: HookId, event: HookEvent, payload: Box<dyn Any + Send>) -> Self {
+        HookPanic {
+            hook_id,
+            event,
+            message: panic_message(&*payload),
+            payload,
         }
     }
 }
 
-#[cfg(target_os = "windows")]
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "no panic info".to_string()
+    }
+}
+
+/// An error returned when writing or reading a recorded session, see the [`recording`] module.
+///
+/// [`recording`]: crate::recording
+#[cfg_attr(rustdoc, doc(cfg(feature = "recording")))]
+#[cfg(feature = "recording")]
 #[derive(Debug, Error)]
-pub enum HookError {
-    #[error("Failed to allocate memory.")]
+pub enum RecordingError {
+    /// Reading from or writing to the underlying file failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// A line of a JSON Lines session could not be encoded or decoded.
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A record of a binary session could not be encoded or decoded.
+    #[error("Invalid binary record: {0}")]
+    Binary(#[from] bincode::Error),
+    /// The data is neither a JSON Lines nor a binary session.
+    #[error("Not a recorded session")]
+    UnknownFormat,
+    /// The session was recorded with a newer version of the format.
+    #[error("Unsupported session format version {0}")]
+    UnsupportedVersion(u32),
+    /// The recorder was already stopped, a recorder only records a single session.
+    #[error("The recorder was already stopped")]
+    Stopped,
+}
+
+/// A portable classification of a [`HookError`], it is the same on every platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The native library failed to allocate memory.
     OutOfMemory,
-    #[error("Failed to register native windows hook.")]
-    SetHookEx,
-    #[error("Failed to retrieve handle for native windows hook.")]
-    GetModuleHandle,
-    #[error("Encountered unknown error.")]
-    Unknown(&'static str, Box<dyn Any + Send + 'static>),
+    /// The display server could not be reached, for example the X11 display could not be opened.
+    DisplayUnavailable,
+    /// The process is not allowed to hook the input devices, for example on macOS
+    /// access for assistive devices is not enabled.
+    PermissionDenied,
+    /// The operating system facility used to hook the input devices could not be set up.
+    HookUnavailable,
+    /// The hook was not enabled in time, see [`hook_start_with_timeout`].
+    ///
+    /// [`hook_start_with_timeout`]: crate::hook::global::hook_start_with_timeout
+    TimedOut,
+    /// The hook is already running.
+    AlreadyRunning,
+    /// The event cannot be posted, control events such as `Enabled` and `Disabled` cannot be posted.
+    InvalidEvent,
+    /// One of the threads running the hook panicked, see [`HookError::into_panic`].
+    Panic,
+    /// Any other error, including native status codes that are not recognized by this library.
+    Other,
 }
 
-#[cfg(target_os = "windows")]
-impl From<u32> for HookError {
-    fn from(native_error: u32) -> Self {
-        match native_error {
-            ffi::UIOHOOK_ERROR_OUT_OF_MEMORY => HookError::OutOfMemory,
-            ffi::UIOHOOK_ERROR_SET_WINDOWS_HOOK_EX => HookError::SetHookEx,
-            ffi::UIOHOOK_ERROR_GET_MODULE_HANDLE => HookError::GetModuleHandle,
-            _ => HookError::Unknown("unknown error code", Box::new(())),
-        }
+/// The operation that failed with a [`HookError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Starting the hook, until it is enabled.
+    Start,
+    /// Running the hook, after it was enabled.
+    Run,
+    /// Stopping the hook.
+    Stop,
+    /// Posting an event.
+    Post,
+    /// Querying the screens, see [`screen_info`].
+    ///
+    /// [`screen_info`]: crate::system_properties::screen_info
+    ScreenQuery,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operation = match self {
+            Operation::Start => "start the hook",
+            Operation::Run => "run the hook",
+            Operation::Stop => "stop the hook",
+            Operation::Post => "post the event",
+            Operation::ScreenQuery => "query the screens",
+        };
+        f.write_str(operation)
     }
 }
 
-#[cfg(target_os = "macos")]
-#[derive(Debug, Error)]
-pub enum HookError {
-    #[error("Failed to allocate memory.")]
-    OutOfMemory,
-    #[error("Failed to enable access for assistive devices.")]
+/// The platform specific cause of a [`HookError`], as reported by libuiohook.
+///
+/// All the variants are available on every platform so they can be matched without cfg blocks,
+/// though each platform only reports its own variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NativeError {
+    /// Linux, failed to open X11 display.
+    XOpenDisplay,
+    /// Linux, unable to locate XRecord extension.
+    XRecordNotFound,
+    /// Linux, unable to allocate XRecord range.
+    XRecordAllocRange,
+    /// Linux, unable to allocate XRecord context.
+    XRecordCreateContext,
+    /// Linux, failed to enable XRecord context.
+    XRecordEnableContext,
+    /// Linux, could not retrieve XRecord context.
+    XRecordGetContext,
+    /// Windows, failed to register native windows hook.
+    SetWindowsHookEx,
+    /// Windows, failed to retrieve handle for native windows hook.
+    GetModuleHandle,
+    /// macOS, failed to enable access for assistive devices.
     AXAPIDisabled,
-    #[error("Failed to create apple event port.")]
+    /// macOS, failed to create apple event port.
     CreateEventPort,
-    #[error("Failed to create apple run loop source.")]
+    /// macOS, failed to create apple run loop source.
     CreateRunLoopSource,
-    #[error("Failed to acquire apple run loop.")]
+    /// macOS, failed to acquire apple run loop.
     GetRunLoop,
-    #[error("Failed to create apple run loop observer.")]
+    /// macOS, failed to create apple run loop observer.
     CreateObserver,
-    #[error("Encountered unknown error.")]
-    Unknown(&'static str, Box<dyn Any + Send + 'static>),
 }
 
-#[cfg(target_os = "macos")]
-impl From<u32> for HookError {
-    fn from(native_error: u32) -> Self {
-        match native_error {
-            ffi::UIOHOOK_ERROR_OUT_OF_MEMORY => HookError::OutOfMemory,
-            ffi::UIOHOOK_ERROR_SET_WINDOWS_HOOK_EX => HookError::AXAPIDisabled,
-            ffi::UIOHOOK_ERROR_GET_MODULE_HANDLE => HookError::CreateEventPort,
-            ffi::UIOHOOK_ERROR_CREATE_RUN_LOOP_SOURCE => HookError::CreateRunLoopSource,
-            ffi::UIOHOOK_ERROR_GET_RUNLOOP => HookError::GetRunLoop,
-            ffi::UIOHOOK_ERROR_CREATE_OBSERVER => HookError::CreateObserver,
-            _ => HookError::Unknown("unknown error code", Box::new(())),
+/// An error returned by the hook, it is the same type on every platform.
+///
+/// Every error has a portable [`ErrorKind`] and the [`Operation`] that failed, errors reported by libuiohook
+/// also carry the raw native status code, and the platform specific [`NativeError`] if the code is recognized.
+///
+/// # Example
+/// ```rust
+/// use uiohook_rs::{ErrorKind, HookError, Operation};
+///
+/// fn describe(err: &HookError) -> &'static str {
+///     match (err.kind(), err.operation()) {
+///         (ErrorKind::DisplayUnavailable, _) => "is the display server running?",
+///         (ErrorKind::PermissionDenied, _) => "please grant the accessibility permission",
+///         (_, Operation::Stop) => "the hook could not be stopped",
+///         _ => "something went wrong",
+///     }
+/// }
+///
+/// let err = HookError::new(ErrorKind::PermissionDenied, Operation::Start, "no access");
+/// assert_eq!(describe(&err), "please grant the accessibility permission");
+/// ```
+#[derive(Debug, Error)]
+#[error("Failed to {operation}: {message}")]
+pub struct HookError {
+    kind: ErrorKind,
+    operation: Operation,
+    message: Cow<'static, str>,
+    code: Option<u32>,
+    native: Option<NativeError>,
+    payload: Option<Box<dyn Any + Send + 'static>>,
+}
+
+impl HookError {
+    /// Create an error, this is mostly useful for implementing a [`Backend`].
+    ///
+    /// [`Backend`]: crate::hook::backend::Backend
+    pub fn new<M: Into<Cow<'static, str>>>(
+        kind: ErrorKind,
+        operation: Operation,
+        message: M,
+    ) -> Self {
+        HookError {
+            kind,
+            operation,
+            message: message.into(),
+            code: None,
+            native: None,
+            payload: None,
+        }
+    }
+
+    /// Create an error from a status code returned by libuiohook.
+    pub fn from_native(code: u32, operation: Operation) -> Self {
+        // The error codes of the different platforms do not overlap, so we can map all of them
+        // regardless of the current platform.
+        let (kind, native, message) = match code {
+            ffi::UIOHOOK_ERROR_OUT_OF_MEMORY => {
+                (ErrorKind::OutOfMemory, None, "Failed to allocate memory.")
+            }
+            ffi::UIOHOOK_ERROR_X_OPEN_DISPLAY => (
+                ErrorKind::DisplayUnavailable,
+                Some(NativeError::XOpenDisplay),
+                "Failed to open X11 display.",
+            ),
+            ffi::UIOHOOK_ERROR_X_RECORD_NOT_FOUND => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::XRecordNotFound),
+                "Unable to locate XRecord extension.",
+            ),
+            ffi::UIOHOOK_ERROR_X_RECORD_ALLOC_RANGE => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::XRecordAllocRange),
+                "Unable to allocate XRecord range.",
+            ),
+            ffi::UIOHOOK_ERROR_X_RECORD_CREATE_CONTEXT => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::XRecordCreateContext),
+                "Unable to allocate XRecord context.",
+            ),
+            ffi::UIOHOOK_ERROR_X_RECORD_ENABLE_CONTEXT => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::XRecordEnableContext),
+                "Failed to enable XRecord context.",
+            ),
+            ffi::UIOHOOK_ERROR_X_RECORD_GET_CONTEXT => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::XRecordGetContext),
+                "Could not retrieve XRecord context.",
+            ),
+            ffi::UIOHOOK_ERROR_SET_WINDOWS_HOOK_EX => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::SetWindowsHookEx),
+                "Failed to register native windows hook.",
+            ),
+            ffi::UIOHOOK_ERROR_GET_MODULE_HANDLE => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::GetModuleHandle),
+                "Failed to retrieve handle for native windows hook.",
+            ),
+            ffi::UIOHOOK_ERROR_AXAPI_DISABLED => (
+                ErrorKind::PermissionDenied,
+                Some(NativeError::AXAPIDisabled),
+                "Failed to enable access for assistive devices.",
+            ),
+            ffi::UIOHOOK_ERROR_CREATE_EVENT_PORT => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::CreateEventPort),
+                "Failed to create apple event port.",
+            ),
+            ffi::UIOHOOK_ERROR_CREATE_RUN_LOOP_SOURCE => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::CreateRunLoopSource),
+                "Failed to create apple run loop source.",
+            ),
+            ffi::UIOHOOK_ERROR_GET_RUNLOOP => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::GetRunLoop),
+                "Failed to acquire apple run loop.",
+            ),
+            ffi::UIOHOOK_ERROR_CREATE_OBSERVER => (
+                ErrorKind::HookUnavailable,
+                Some(NativeError::CreateObserver),
+                "Failed to create apple run loop observer.",
+            ),
+            _ => (ErrorKind::Other, None, "Encountered unknown error."),
+        };
+
+        HookError {
+            code: Some(code),
+            native,
+            ..HookError::new(kind, operation, message)
+        }
+    }
+
+    pub(crate) fn timed_out(timeout: Duration) -> Self {
+        HookError::new(
+            ErrorKind::TimedOut,
+            Operation::Start,
+            format!("The hook was not enabled within {:?}.", timeout),
+        )
+    }
+
+    pub(crate) fn already_running() -> Self {
+        HookError::new(
+            ErrorKind::AlreadyRunning,
+            Operation::Start,
+            "The hook is already running.",
+        )
+    }
+
+    pub(crate) fn invalid_event(kind: &str) -> Self {
+        HookError::new(
+            ErrorKind::InvalidEvent,
+            Operation::Post,
+            format!(
+                "Trying to post invalid event type `{}`, control events such as Enable and Disable cannot be posted. \
+                Please use hook_start, hook_stop or similar APIs.",
+                kind
+            ),
+        )
+    }
+
+    /// The portable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The operation that failed.
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// A human readable description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The raw status code returned by libuiohook, if the error was reported by the native library.
+    pub fn code(&self) -> Option<u32> {
+        self.code
+    }
+
+    /// The platform specific cause of the error, if the error was reported by the native library
+    /// and its status code is recognized.
+    pub fn native(&self) -> Option<NativeError> {
+        self.native
+    }
+
+    /// Take the panic payload of an error of kind [`ErrorKind::Panic`], it can be used to continue
+    /// the panic with [`std::panic::resume_unwind`].
+    pub fn into_panic(self) -> Option<Box<dyn Any + Send + 'static>> {
+        self.payload
+    }
+
+    pub(crate) fn with_operation(mut self, operation: Operation) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    // The native hook is shared by all the hook sessions, so when it fails the error has to be reported
+    // to each one of them. The panic payload cannot be cloned, so duplicates do not carry one.
+    pub(crate) fn duplicate(&self) -> Self {
+        HookError {
+            kind: self.kind,
+            operation: self.operation,
+            message: self.message.clone(),
+            code: self.code,
+            native: self.native,
+            payload: None,
         }
     }
 }
 
 impl From<Box<dyn Any + Send + 'static>> for HookError {
     fn from(thread_panic: Box<dyn Any + Send + 'static>) -> Self {
-        if let Some(s) = thread_panic.downcast_ref::<&'static str>() {
-            HookError::Unknown(s, thread_panic)
-        } else {
-            HookError::Unknown("no panic info", thread_panic)
+        let message = format!(
+            "A thread running the hook panicked: {}",
+            panic_message(&*thread_panic)
+        );
+        HookError {
+            payload: Some(thread_panic),
+            ..HookError::new(ErrorKind::Panic, Operation::Run, message)
         }
     }
 }
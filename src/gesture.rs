@@ -0,0 +1,386 @@
+//! Recognize multi-clicks, long presses and hold-drags from the mouse events.
+//!
+//! The click count reported by the operating system in [`MouseEvent::clicks`] is computed differently on every
+//! platform, and is not computed at all for synthetic events. A [`GestureRecognizer`] counts the clicks itself,
+//! from the timestamps and positions of the press and release events, so a double click means the same thing
+//! everywhere:
+//!
+//! *   A click is a press and a release of the same button, without moving the cursor further than the distance
+//!     tolerance from where the button was pressed, see [`Gesture::Click`].
+//! *   A click is counted as part of the previous click, making it a double or triple click, if the button was
+//!     pressed within the multi-click time of the previous press, and within the distance tolerance of it.
+//! *   Holding the button still for the long-press time is a long press, and is not a click,
+//!     see [`Gesture::LongPress`].
+//! *   Moving the cursor after a long press is a hold-drag, see [`Gesture::HoldDragStart`].
+//!
+//! By default the multi-click time and the long-press time are both taken from
+//! [`system_properties::multi_click_time`], and the distance tolerance is 4 pixels.
+//!
+//! # Example
+//! ```rust
+//! use uiohook_rs::gesture::{Gesture, GestureRecognizer};
+//! use uiohook_rs::hook::event::MouseButton;
+//! use uiohook_rs::HookEvent;
+//! use std::time::Duration;
+//!
+//! let mut recognizer = GestureRecognizer::new()
+//!     .with_multi_click_time(Duration::from_millis(500))
+//!     .with_long_press_time(Duration::from_millis(500));
+//! let mut feed = |mut event: HookEvent, time: u128| {
+//!     event.metadata.time = time;
+//!     recognizer.feed(&event)
+//! };
+//! let left = || HookEvent::mouse(MouseButton::Left).with_position(10, 10);
+//!
+//! // two quick clicks, the second one slightly off.
+//! feed(left().press(), 1000);
+//! let first = feed(left().release(), 1080);
+//! feed(left().with_position(12, 11).press(), 1200);
+//! let second = feed(left().with_position(12, 11).release(), 1260);
+//! assert!(matches!(first[..], [Gesture::Click { count: 1, .. }]));
+//! assert!(matches!(second[..], [Gesture::Click { count: 2, .. }]));
+//!
+//! // a press that is held is a long press rather than a third click.
+//! feed(left().press(), 1400);
+//! let held = feed(left().release(), 2000);
+//! assert!(matches!(held[..], [Gesture::LongPress { .. }]));
+//! ```
+//!
+//! [`MouseEvent::clicks`]: crate::hook::event::MouseEvent::clicks
+//! [`system_properties::multi_click_time`]: crate::system_properties::multi_click_time
+
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::hook::event::{now, EventKind, HookEvent, MouseButton};
+use crate::hook::Hook;
+use crate::system_properties;
+
+/// A gesture recognized by a [`GestureRecognizer`], positions are in screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    /// The button was clicked, `count` is 1 for a single click, 2 for a double click, 3 for a triple click
+    /// and so on. Every click of a multi-click is reported, so a double click is preceded by a single click.
+    Click {
+        button: MouseButton,
+        position: (i16, i16),
+        count: u32,
+    },
+    /// The button was held still for the long-press time, it is reported while the button is still held.
+    LongPress {
+        button: MouseButton,
+        position: (i16, i16),
+    },
+    /// The cursor moved away after a long press.
+    HoldDragStart {
+        button: MouseButton,
+        origin: (i16, i16),
+    },
+    /// The cursor moved during a hold-drag.
+    HoldDragMove {
+        button: MouseButton,
+        origin: (i16, i16),
+        position: (i16, i16),
+    },
+    /// The button was released, ending a hold-drag.
+    HoldDragEnd {
+        button: MouseButton,
+        origin: (i16, i16),
+        position: (i16, i16),
+    },
+}
+
+struct Press {
+    button: MouseButton,
+    origin: (i16, i16),
+    time: u128,
+    moved: bool,
+    long: bool,
+}
+
+struct LastClick {
+    button: MouseButton,
+    position: (i16, i16),
+    // The time of the press of the click.
+    time: u128,
+    count: u32,
+}
+
+/// Turns mouse events into gestures, see the [module level documentation](crate::gesture).
+pub struct GestureRecognizer {
+    multi_click_time: u128,
+    long_press_time: u128,
+    distance: u16,
+    press: Option<Press>,
+    last_click: Option<LastClick>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureRecognizer {
+    /// Create a recognizer with the multi-click time of the system, see the [module level documentation](crate::gesture).
+    pub fn new() -> Self {
+        // 500 milliseconds is the default of most systems.
+        let multi_click_time = system_properties::multi_click_time().unwrap_or(500) as u128;
+        GestureRecognizer {
+            multi_click_time,
+            long_press_time: multi_click_time,
+            distance: 4,
+            press: None,
+            last_click: None,
+        }
+    }
+
+    /// Set the longest time between the presses of two clicks that are counted as a multi-click.
+    pub fn with_multi_click_time(mut self, time: Duration) -> Self {
+        self.multi_click_time = time.as_millis();
+        self
+    }
+
+    /// Set how long the button has to be held still to be a long press.
+    pub fn with_long_press_time(mut self, time: Duration) -> Self {
+        self.long_press_time = time.as_millis();
+        self
+    }
+
+    /// Set how far, in pixels both horizontally and vertically, the cursor can move during a click,
+    /// and between the clicks of a multi-click.
+    pub fn with_distance(mut self, distance: u16) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Update the recognizer from an event, and return the gestures it completes.
+    ///
+    /// Events that are not mouse events are ignored, only the first of several held buttons is tracked.
+    pub fn feed(&mut self, event: &HookEvent) -> Vec<Gesture> {
+        let time = match event.metadata.time {
+            0 => now(),
+            time => time,
+        };
+        let mut gestures = self.poll_at(time);
+
+        match &event.kind {
+            EventKind::MousePressed(data) if self.press.is_none() => {
+                self.press = Some(Press {
+                    button: data.button,
+                    origin: (data.x, data.y),
+                    time,
+                    moved: false,
+                    long: false,
+                });
+            }
+            EventKind::MouseMoved(data) | EventKind::MouseDragged(data) => {
+                let distance = self.distance;
+                if let Some(press) = &mut self.press {
+                    let position = (data.x, data.y);
+                    if !press.moved && !within(press.origin, position, distance) {
+                        press.moved = true;
+                        if press.long {
+                            gestures.push(Gesture::HoldDragStart {
+                                button: press.button,
+                                origin: press.origin,
+                            });
+                        }
+                    }
+                    if press.moved && press.long {
+                        gestures.push(Gesture::HoldDragMove {
+                            button: press.button,
+                            origin: press.origin,
+                            position,
+                        });
+                    }
+                }
+            }
+            EventKind::MouseReleased(data) if matches!(&self.press, Some(press) if press.button == data.button) =>
+            {
+                let position = (data.x, data.y);
+                let press = self.press.take().unwrap();
+                if press.long {
+                    self.last_click = None;
+                    if press.moved {
+                        gestures.push(Gesture::HoldDragEnd {
+                            button: press.button,
+                            origin: press.origin,
+                            position,
+                        });
+                    }
+                } else if press.moved || !within(press.origin, position, self.distance) {
+                    self.last_click = None;
+                } else {
+                    gestures.push(self.click(&press, position));
+                }
+            }
+            EventKind::Enabled | EventKind::Disabled => {
+                self.press = None;
+                self.last_click = None;
+            }
+            _ => (),
+        }
+
+        gestures
+    }
+
+    /// Report a long press of the held button once the long-press time elapsed,
+    /// even though no event was received since the button was pressed.
+    ///
+    /// The hook returned by [`into_hook`](GestureRecognizer::into_hook) takes care of calling this method.
+    pub fn poll(&mut self) -> Vec<Gesture> {
+        self.poll_at(now())
+    }
+
+    /// Turn the recognizer into a hook that calls `callback` with every recognized gesture.
+    ///
+    /// The hook uses a timer thread to report long presses while the button is held,
+    /// the callback is called either on the thread that calls the hook, or on the timer thread,
+    /// though never from both at once, and always in the order the gestures were recognized.
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::mpsc::channel;
+    /// use std::sync::Mutex;
+    /// use std::time::Duration;
+    ///
+    /// use uiohook_rs::gesture::{Gesture, GestureRecognizer};
+    /// use uiohook_rs::hook::event::MouseButton;
+    /// use uiohook_rs::{hook_start, HookEvent};
+    ///
+    /// let (sender, gestures) = channel();
+    /// let sender = Mutex::new(sender);
+    /// let mut hook = GestureRecognizer::new()
+    ///     .with_long_press_time(Duration::from_millis(50))
+    ///     .into_hook(move |gesture| sender.lock().unwrap().send(*gesture).unwrap());
+    /// hook.register();
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// HookEvent::mouse(MouseButton::Left).press().post().unwrap();
+    ///
+    /// // the long press is reported while the button is still held.
+    /// let gesture = gestures.recv_timeout(Duration::from_secs(5)).unwrap();
+    /// assert!(matches!(gesture, Gesture::LongPress { button: MouseButton::Left, .. }));
+    ///
+    /// HookEvent::mouse(MouseButton::Left).release().post().unwrap();
+    /// handle.stop().unwrap();
+    /// ```
+    pub fn into_hook<F: Fn(&Gesture) + Sync + Send + 'static>(self, callback: F) -> Hook {
+        let shared = Arc::new(Shared {
+            recognizer: Mutex::new(self),
+            cond: Condvar::new(),
+            delivery: Mutex::new(()),
+        });
+        let callback = Arc::new(callback);
+
+        let timer_shared = Arc::downgrade(&shared);
+        let timer_callback = callback.clone();
+        thread::spawn(move || run_timer(timer_shared, &*timer_callback));
+
+        Hook::new(move |event| {
+            let _delivery = shared.delivery.lock();
+            let gestures = {
+                let mut recognizer = shared.recognizer.lock();
+                let gestures = recognizer.feed(event);
+                // wake the timer up, a press might have started.
+                shared.cond.notify_one();
+                gestures
+            };
+            for gesture in &gestures {
+                callback(gesture);
+            }
+        })
+    }
+
+    /// The time the held button becomes a long press, if it is not one yet.
+    fn deadline(&self) -> Option<u128> {
+        match &self.press {
+            Some(press) if !press.long && !press.moved => Some(press.time + self.long_press_time),
+            _ => None,
+        }
+    }
+
+    fn poll_at(&mut self, time: u128) -> Vec<Gesture> {
+        match self.deadline() {
+            Some(deadline) if time >= deadline => {
+                let press = self.press.as_mut().unwrap();
+                press.long = true;
+                vec![Gesture::LongPress {
+                    button: press.button,
+                    position: press.origin,
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn click(&mut self, press: &Press, position: (i16, i16)) -> Gesture {
+        let count = match &self.last_click {
+            Some(last)
+                if last.button == press.button
+                    && press.time.saturating_sub(last.time) <= self.multi_click_time
+                    && within(last.position, position, self.distance) =>
+            {
+                last.count + 1
+            }
+            _ => 1,
+        };
+
+        self.last_click = Some(LastClick {
+            button: press.button,
+            position,
+            time: press.time,
+            count,
+        });
+        Gesture::Click {
+            button: press.button,
+            position,
+            count,
+        }
+    }
+}
+
+struct Shared {
+    recognizer: Mutex<GestureRecognizer>,
+    cond: Condvar,
+    // Held from recognizing gestures until the callback is done with them, so that the gestures
+    // recognized by the hook and by the timer are delivered in order. It is locked before the recognizer.
+    delivery: Mutex<()>,
+}
+
+fn run_timer(shared: Weak<Shared>, callback: &(dyn Fn(&Gesture) + Sync + Send)) {
+    // The thread exits once the hook, which owns the recognizer, is dropped.
+    while let Some(shared) = shared.upgrade() {
+        {
+            let mut recognizer = shared.recognizer.lock();
+            let wait = match recognizer.deadline() {
+                Some(deadline) => deadline.saturating_sub(now()) as u64,
+                None => 1000,
+            };
+            if wait > 0 {
+                shared
+                    .cond
+                    .wait_for(&mut recognizer, Duration::from_millis(wait));
+                continue;
+            }
+        }
+
+        // The hook might have handled an event in between, so the recognizer is polled again.
+        let _delivery = shared.delivery.lock();
+        let gestures = shared.recognizer.lock().poll();
+        for gesture in &gestures {
+            callback(gesture);
+        }
+    }
+}
+
+fn within(a: (i16, i16), b: (i16, i16), distance: u16) -> bool {
+    let dx = (i32::from(a.0) - i32::from(b.0)).unsigned_abs();
+    let dy = (i32::from(a.1) - i32::from(b.1)).unsigned_abs();
+    dx <= u32::from(distance) && dy <= u32::from(distance)
+}
@@ -0,0 +1,413 @@
+//! Input backends driving the hook.
+//!
+//! A [`Backend`] is the source of every event distributed to the registered hooks, and the sink
+//! for every event posted through [`post_event`]. The control and hook threads described in the
+//! [`global`] module documentation do not talk to the operating system directly, instead they run
+//! against the currently installed backend.
+//!
+//! By default the library uses [`NativeBackend`] which is backed by libuiohook, though it can be
+//! replaced using [`set_backend`] in order to plug in alternative input sources, or to drive the whole
+//! dispatch pipeline in tests without access to a display server.
+//!
+//! [`global`]: crate::hook::global
+//! [`post_event`]: crate::hook::global::post_event
+//! [`set_backend`]: crate::hook::global::set_backend
+
+use std::sync::Arc;
+
+use crate::error::HookError;
+use crate::hook::event::HookEvent;
+
+/// Callback used by a [`Backend`] to pass the events it generates into the library.
+///
+/// The callback should be called once for every event the backend generates, including the
+/// [`Enabled`] event when the backend is ready and the [`Disabled`] event right before [`Backend::start`]
+/// returns. The callback might modify the event, specifically it will insert [`EventMode::RESERVED`]
+/// into the event mode if the event was reserved by the filter set with [`reserve_events`], backends that
+/// support reserving events should check for this flag after the callback returns.
+///
+/// [`Enabled`]: crate::hook::event::EventKind::Enabled
+/// [`Disabled`]: crate::hook::event::EventKind::Disabled
+/// [`EventMode::RESERVED`]: crate::hook::event::EventMode::RESERVED
+/// [`reserve_events`]: crate::hook::global::reserve_events
+pub type DispatchProc = Arc<dyn Fn(&mut HookEvent) + Sync + Send>;
+
+/// A source of input events and a sink for posted events.
+///
+/// The methods of this trait map directly to the native functions used by the library,
+/// `hook_run`, `hook_stop`, `hook_post_event` and `hook_set_dispatch_proc` respectively.
+/// Note that the backend is shared between the control thread, the hook thread and any thread
+/// posting events, so all the methods take `&self` and might be called concurrently.
+///
+/// # Example
+/// ```rust
+/// use std::sync::{Condvar, Mutex};
+///
+/// use uiohook_rs::hook::backend::{Backend, DispatchProc};
+/// use uiohook_rs::hook::event::{EventKind, EventMetaData, HookEvent};
+/// use uiohook_rs::HookError;
+///
+/// /// A backend that echoes every posted event back into the hook.
+/// #[derive(Default)]
+/// struct Echo {
+///     dispatch: Mutex<Option<DispatchProc>>,
+///     stopped: (Mutex<bool>, Condvar),
+/// }
+///
+/// impl Echo {
+///     fn dispatch(&self, kind: EventKind) {
+///         let dispatch = self.dispatch.lock().unwrap().clone();
+///         if let Some(dispatch) = dispatch {
+///             let mut event = HookEvent {
+///                 metadata: EventMetaData::default(),
+///                 kind,
+///             };
+///             dispatch(&mut event);
+///         }
+///     }
+/// }
+///
+/// impl Backend for Echo {
+///     fn start(&self) -> Result<(), HookError> {
+///         self.dispatch(EventKind::Enabled);
+///         let (ref lock, ref cond) = self.stopped;
+///         let mut stopped = lock.lock().unwrap();
+///         while !*stopped {
+///             stopped = cond.wait(stopped).unwrap();
+///         }
+///         *stopped = false;
+///         self.dispatch(EventKind::Disabled);
+///         Ok(())
+///     }
+///
+///     fn stop(&self) -> Result<(), HookError> {
+///         let (ref lock, ref cond) = self.stopped;
+///         *lock.lock().unwrap() = true;
+///         cond.notify_all();
+///         Ok(())
+///     }
+///
+///     fn post(&self, event: HookEvent) {
+///         self.dispatch(event.kind);
+///     }
+///
+///     fn set_dispatch_proc(&self, dispatch: Option<DispatchProc>) {
+///         *self.dispatch.lock().unwrap() = dispatch;
+///     }
+/// }
+/// ```
+pub trait Backend: Send + Sync {
+    /// Start the backend, this method should block until the backend is stopped either
+    /// by a call to [`stop`] or due to an error.
+    ///
+    /// The backend should dispatch an [`Enabled`] event once it is ready to generate and post events,
+    /// and a [`Disabled`] event once it is stopped. Note that if this method returns an error without
+    /// dispatching the [`Disabled`] event, the library will dispatch one on its behalf.
+    ///
+    /// [`stop`]: Backend::stop
+    /// [`Enabled`]: crate::hook::event::EventKind::Enabled
+    /// [`Disabled`]: crate::hook::event::EventKind::Disabled
+    fn start(&self) -> Result<(), HookError>;
+
+    /// Stop the backend, causing the call to [`start`] to return.
+    ///
+    /// [`start`]: Backend::start
+    fn stop(&self) -> Result<(), HookError>;
+
+    /// Post an event, the library guarantees that control events (`Enabled` and `Disabled`)
+    /// are never passed to this method.
+    fn post(&self, event: HookEvent);
+
+    /// Set the callback that should receive every event generated by the backend,
+    /// `None` means that the backend should stop dispatching events.
+    fn set_dispatch_proc(&self, dispatch: Option<DispatchProc>);
+}
+
+/// The default backend, generating and posting events using libuiohook.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NativeBackend;
+
+impl Backend for NativeBackend {
+    fn start(&self) -> Result<(), HookError> {
+        #[cfg(feature = "logging")]
+        native::enable_logging();
+        native::hook_start()
+    }
+
+    fn stop(&self) -> Result<(), HookError> {
+        native::hook_stop()
+    }
+
+    fn post(&self, event: HookEvent) {
+        native::post_event(event)
+    }
+
+    fn set_dispatch_proc(&self, dispatch: Option<DispatchProc>) {
+        native::set_dispatch_proc(dispatch)
+    }
+}
+
+mod native {
+    // Cstr is only used in the logger function that is compiled only when the logging
+    // feature is enables.
+    #[allow(unused_imports)]
+    use std::ffi::CStr;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use ffi::uiohook_event;
+    use once_cell::sync::OnceCell;
+    use parking_lot::{const_mutex, const_rwlock, Mutex, RwLock};
+    use uiohook_sys as ffi;
+
+    use super::DispatchProc;
+    use crate::hook::constants::*;
+    use crate::hook::event::{
+        now, EventKind, EventMetaData, HookEvent, KeyboardEvent, MouseEvent, MouseWheelEvent,
+    };
+    use crate::{HookError, Operation};
+
+    static BASE_TIMESTAMP: OnceCell<u128> = OnceCell::new();
+    static SYNTHETIC: AtomicU32 = AtomicU32::new(0);
+    static DISPATCH_PROC: RwLock<Option<DispatchProc>> = const_rwlock(None);
+
+    fn set_timestamp(metadata: &mut EventMetaData) {
+        // libuiohook uses the system uptime as a timestamp, which means that if we shut down the computer,
+        // and then run the library again the timestamp could be smaller than one acquired before shutting
+        // down the computer. We don't want to make the system call for getting the current time on every call,
+        // especially because we already have a timestamp. What we do here is calculate the difference
+        // between unix time stamp and the system uptime, giving us the unix timestamp of the system startup
+        // and in later calls all we need to do is add the system timestamp to the base time we calculated.
+        metadata.time = BASE_TIMESTAMP.get_or_init(|| now().saturating_sub(metadata.time as u128))
+            + metadata.time as u128;
+    }
+
+    fn set_synthetic(rusty_event: &mut HookEvent, native_event: &ffi::uiohook_event) {
+        // we only need this mut on windows to possibly change the type in the following if.
+        #[allow(unused_mut)]
+        let mut event_type = native_event.type_;
+
+        // This is used to make it possible to create synthetic drag events on windows.
+        // Because on windows we cant actually create a drag event,
+        // we need to do a press -> move -> release when the event sent by the OS will be a drag event
+        // but the SYNTHETIC atomic will have the move type.
+        // So on windows we do not distinguish between drag and move events in regard to weather
+        // they are synthetic.
+        #[cfg(target_os = "windows")]
+        if event_type == NativeEventKind::EVENT_MOUSE_DRAGGED {
+            event_type = NativeEventKind::EVENT_MOUSE_MOVED;
+        }
+
+        if SYNTHETIC
+            .compare_exchange(event_type as u32, 0, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            rusty_event.metadata.mode.insert(EventMode::SYNTHETIC);
+        }
+    }
+
+    fn from_native(native: &ffi::uiohook_event) -> HookEvent {
+        let mut meta = EventMetaData {
+            time: native.time as u128,
+            mask: native.mask.into(),
+            mode: EventMode::from_bits(native.reserved).unwrap_or(EventMode::DEFAULT),
+        };
+
+        set_timestamp(&mut meta);
+
+        #[inline(always)]
+        fn from_keyboard(native: &ffi::uiohook_event) -> KeyboardEvent {
+            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
+            unsafe { &native.data.keyboard }.into()
+        }
+
+        #[inline(always)]
+        fn from_mouse(native: &ffi::uiohook_event) -> MouseEvent {
+            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
+            unsafe { &native.data.mouse }.into()
+        }
+
+        #[inline(always)]
+        fn from_mouse_wheel(native: &ffi::uiohook_event) -> MouseWheelEvent {
+            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
+            unsafe { &native.data.wheel }.into()
+        }
+
+        let event_data = match native.type_ {
+            NativeEventKind::EVENT_HOOK_ENABLED => EventKind::Enabled,
+            NativeEventKind::EVENT_HOOK_DISABLED => EventKind::Disabled,
+            NativeEventKind::EVENT_KEY_TYPED => EventKind::KeyTyped(from_keyboard(native)),
+            NativeEventKind::EVENT_KEY_PRESSED => EventKind::KeyPressed(from_keyboard(native)),
+            NativeEventKind::EVENT_KEY_RELEASED => EventKind::KeyReleased(from_keyboard(native)),
+            NativeEventKind::EVENT_MOUSE_CLICKED => EventKind::MouseClicked(from_mouse(native)),
+            NativeEventKind::EVENT_MOUSE_PRESSED => EventKind::MousePressed(from_mouse(native)),
+            NativeEventKind::EVENT_MOUSE_RELEASED => EventKind::MouseReleased(from_mouse(native)),
+            NativeEventKind::EVENT_MOUSE_MOVED => EventKind::MouseMoved(from_mouse(native)),
+            NativeEventKind::EVENT_MOUSE_DRAGGED => EventKind::MouseDragged(from_mouse(native)),
+            NativeEventKind::EVENT_MOUSE_WHEEL => EventKind::MouseWheel(from_mouse_wheel(native)),
+        };
+
+        HookEvent {
+            metadata: meta,
+            kind: event_data,
+        }
+    }
+
+    fn into_native(event: HookEvent) -> ffi::uiohook_event {
+        let mask = event.metadata.mask;
+
+        let (event_type, event_data) = match event.kind {
+            EventKind::Enabled => (
+                NativeEventKind::EVENT_HOOK_ENABLED,
+                ffi::_uiohook_event__bindgen_ty_1::default(),
+            ),
+            EventKind::Disabled => (
+                NativeEventKind::EVENT_HOOK_DISABLED,
+                ffi::_uiohook_event__bindgen_ty_1::default(),
+            ),
+            EventKind::KeyTyped(event_data) => (
+                NativeEventKind::EVENT_KEY_TYPED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    keyboard: event_data.into(),
+                },
+            ),
+            EventKind::KeyPressed(event_data) => (
+                NativeEventKind::EVENT_KEY_PRESSED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    keyboard: event_data.into(),
+                },
+            ),
+            EventKind::KeyReleased(event_data) => (
+                NativeEventKind::EVENT_KEY_RELEASED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    keyboard: event_data.into(),
+                },
+            ),
+            EventKind::MouseClicked(event_data) => (
+                NativeEventKind::EVENT_MOUSE_CLICKED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    mouse: event_data.into(),
+                },
+            ),
+            EventKind::MousePressed(event_data) => (
+                NativeEventKind::EVENT_MOUSE_PRESSED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    mouse: event_data.into(),
+                },
+            ),
+            EventKind::MouseReleased(event_data) => (
+                NativeEventKind::EVENT_MOUSE_RELEASED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    mouse: event_data.into(),
+                },
+            ),
+            EventKind::MouseMoved(event_data) => (
+                NativeEventKind::EVENT_MOUSE_MOVED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    mouse: event_data.into(),
+                },
+            ),
+            EventKind::MouseDragged(event_data) => (
+                NativeEventKind::EVENT_MOUSE_DRAGGED,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    mouse: event_data.into(),
+                },
+            ),
+            EventKind::MouseWheel(event_data) => (
+                NativeEventKind::EVENT_MOUSE_WHEEL,
+                ffi::_uiohook_event__bindgen_ty_1 {
+                    wheel: event_data.into(),
+                },
+            ),
+        };
+
+        ffi::uiohook_event {
+            type_: event_type,
+            data: event_data,
+            // we dont need to set the meta data here, since it will is ignored when the event is posted,
+            // and the OS will create its own meta data.
+            time: 0,
+            mask: mask.into(),
+            reserved: 0,
+        }
+    }
+
+    extern "C" fn event_handler(event: *mut ffi::uiohook_event) {
+        let dispatch = match &*DISPATCH_PROC.read() {
+            Some(dispatch) => dispatch.clone(),
+            None => return,
+        };
+
+        // SAFETY: We assume that if the pointer is pointing to a valid uiohook_event,
+        // as specified by the native library. Beyond that we only use the original event data
+        // in order to construct our own representation, once the HookEvent struct is created it will
+        // be used and the original pointer and data will not be read or mutated, except for the
+        // reserved flag which the OS expects us to set from within this handler.
+        // This means that the pointer can be safely freed when this function is complete.
+        if let Some(native_event) = unsafe { event.as_mut() } {
+            let mut rusty_event = from_native(native_event);
+            set_synthetic(&mut rusty_event, native_event);
+
+            dispatch(&mut rusty_event);
+
+            if rusty_event.metadata.mode.contains(EventMode::RESERVED) {
+                native_event.reserved = EventMode::RESERVED.bits();
+            }
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    extern "C" fn logger(level: ffi::log_level, raw_message: *const std::os::raw::c_char) -> bool {
+        match unsafe { CStr::from_ptr(raw_message) }.to_str() {
+            Ok(log_message) => match level {
+                ffi::log_level::LOG_LEVEL_INFO => log::info!("{}", log_message),
+                ffi::log_level::LOG_LEVEL_DEBUG => log::debug!("{}", log_message),
+                ffi::log_level::LOG_LEVEL_WARN => log::warn!("{}", log_message),
+                ffi::log_level::LOG_LEVEL_ERROR => log::error!("{}", log_message),
+            },
+            Err(_) => return false,
+        }
+
+        return true;
+    }
+
+    #[cfg(feature = "logging")]
+    pub fn enable_logging() {
+        unsafe { ffi::hook_set_rusty_logger(Some(logger)) }
+    }
+
+    pub fn set_dispatch_proc(dispatch: Option<DispatchProc>) {
+        let installed = dispatch.is_some();
+        *DISPATCH_PROC.write() = dispatch;
+
+        if installed {
+            unsafe { ffi::hook_set_dispatch_proc(Some(event_handler)) }
+        } else {
+            unsafe { ffi::hook_set_dispatch_proc(None) }
+        }
+    }
+
+    pub fn post_event(event: HookEvent) {
+        static POST_MUTEX: Mutex<()> = const_mutex(());
+
+        let mut native_event = into_native(event);
+        let _guard = POST_MUTEX.lock();
+        SYNTHETIC.store(native_event.type_ as u32, Ordering::SeqCst);
+        unsafe {
+            ffi::hook_post_event(&mut native_event as *mut uiohook_event);
+        };
+    }
+
+    pub fn hook_start() -> Result<(), HookError> {
+        match unsafe { ffi::hook_run() as u32 } {
+            ffi::UIOHOOK_SUCCESS => Ok(()),
+            status => Err(HookError::from_native(status, Operation::Start)),
+        }
+    }
+
+    pub fn hook_stop() -> Result<(), HookError> {
+        match unsafe { ffi::hook_stop() as u32 } {
+            ffi::UIOHOOK_SUCCESS => Ok(()),
+            status => Err(HookError::from_native(status, Operation::Stop)),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! The event bus connecting the native hook to the control thread of a session.
+//!
+//! By default the bus is unbounded, meaning that if the registered hooks are slower than the rate in
+//! which the user generates events (mouse movement can easily generate hundreds of events per second),
+//! the events will keep accumulating in memory. A session can be configured with a capacity for its bus,
+//! along with an [`OverflowPolicy`] that decides what happens to new events when the bus is full.
+//!
+//! Every event that is lost because of the overflow policy is counted, see [`HookSession::dropped_events`].
+//!
+//! The hooks that are not called on the control thread, see the [`dispatch`] module, receive the events
+//! through the queue of their worker thread, which has the same capacity and overflow policy as the bus,
+//! so a slow hook cannot make its queue grow without limit either.
+//!
+//! Note that the [`Enabled`] and [`Disabled`] control events are never dropped, and are accepted by the bus
+//! even when it is full, this guarantees that a session can always be stopped.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::thread::sleep;
+//! use std::time::Duration;
+//!
+//! use uiohook_rs::hook::bus::OverflowPolicy;
+//! use uiohook_rs::hook::event::MouseButton;
+//! use uiohook_rs::hook::session::HookSession;
+//! use uiohook_rs::HookEvent;
+//!
+//! // a bus that holds at most 4 events, and merges mouse movement when it is full.
+//! let session = HookSession::with_capacity(4, OverflowPolicy::Coalesce);
+//! session.register_hook(|_| sleep(Duration::from_millis(5)));
+//!
+//! let handle = session.start().expect("oops session already running");
+//! for i in 0..20 {
+//!     HookEvent::mouse(MouseButton::NoButton).moved(i, i).post().unwrap();
+//! }
+//! handle.stop().unwrap();
+//!
+//! // the hook could not keep up, so some of the movement was merged.
+//! assert!(session.dropped_events() > 0);
+//! ```
+//!
+//! [`HookSession::dropped_events`]: crate::hook::session::HookSession::dropped_events
+//! [`dispatch`]: crate::hook::dispatch
+//! [`Enabled`]: crate::hook::event::EventKind::Enabled
+//! [`Disabled`]: crate::hook::event::EventKind::Disabled
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::{const_mutex, Condvar, Mutex};
+
+use crate::hook::event::{EventKind, HookEvent};
+
+/// What to do with a new event when the event bus is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until the control thread makes room for the event.
+    ///
+    /// No events are lost, but while the bus is full the native hook is blocked, which might cause
+    /// some operating systems to discard events or make the whole system unresponsive.
+    Block,
+    /// Drop the new event, keeping the events already in the bus.
+    DropNewest,
+    /// Drop the oldest event in the bus to make room for the new event.
+    DropOldest,
+    /// Merge consecutive [`MouseMoved`] or [`MouseDragged`] events, keeping only the latest one.
+    ///
+    /// If the new event cannot be merged with the last event in the bus, this policy behaves
+    /// like [`Block`], so that only mouse movement is ever lost.
+    ///
+    /// [`MouseMoved`]: crate::hook::event::EventKind::MouseMoved
+    /// [`MouseDragged`]: crate::hook::event::EventKind::MouseDragged
+    /// [`Block`]: OverflowPolicy::Block
+    Coalesce,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Block
+    }
+}
+
+struct QueueState<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    // The bus is closed while the control thread is not listening, events sent to a closed
+    // bus are discarded so that senders never wait for a control thread that does not exist.
+    closed: bool,
+}
+
+/// What the overflow policy needs to know about the items of a queue.
+pub(crate) trait Queued {
+    /// Control items are never dropped, and are accepted even when the queue is full.
+    fn is_control(&self) -> bool;
+
+    /// Check if `next` can replace this item with the [`Coalesce`](OverflowPolicy::Coalesce) policy.
+    fn can_coalesce(&self, next: &Self) -> bool;
+}
+
+impl Queued for HookEvent {
+    fn is_control(&self) -> bool {
+        matches!(self.kind, EventKind::Enabled | EventKind::Disabled)
+    }
+
+    fn can_coalesce(&self, next: &Self) -> bool {
+        matches!(
+            (&self.kind, &next.kind),
+            (EventKind::MouseMoved(_), EventKind::MouseMoved(_))
+                | (EventKind::MouseDragged(_), EventKind::MouseDragged(_))
+        )
+    }
+}
+
+/// A queue bounded by a capacity and an overflow policy.
+///
+/// The event bus is one, and so is the queue of every worker thread of a session, which share the
+/// configuration and the dropped counter of the bus so that slow hooks cannot grow them without limit.
+pub(crate) struct EventQueue<T> {
+    state: Mutex<QueueState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    dropped: Arc<AtomicU64>,
+}
+
+pub(crate) type EventBus = EventQueue<HookEvent>;
+
+/// A bus that can never hold an event would block or drop every event, so it holds at least one.
+fn at_least_one(capacity: usize) -> usize {
+    capacity.max(1)
+}
+
+impl EventBus {
+    pub(crate) fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        EventQueue {
+            state: const_mutex(QueueState {
+                queue: VecDeque::new(),
+                capacity: capacity.map(at_least_one),
+                policy,
+                closed: true,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl<T: Queued> EventQueue<T> {
+    /// Create an open queue with the same capacity and overflow policy, counting its dropped items
+    /// along with the items dropped by this queue.
+    pub(crate) fn sibling<U>(&self) -> EventQueue<U> {
+        let state = self.state.lock();
+        EventQueue {
+            state: const_mutex(QueueState {
+                queue: VecDeque::new(),
+                capacity: state.capacity,
+                policy: state.policy,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    pub(crate) fn configure(&self, capacity: Option<usize>, policy: OverflowPolicy) {
+        let mut state = self.state.lock();
+        state.capacity = capacity.map(at_least_one);
+        state.policy = policy;
+        // If the capacity grew, or the policy is no longer blocking, blocked senders can make progress.
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().queue.len()
+    }
+
+    /// Start accepting items, discarding anything left from a previous run.
+    pub(crate) fn open(&self) {
+        let mut state = self.state.lock();
+        state.queue.clear();
+        state.closed = false;
+    }
+
+    /// Stop accepting items, and release all the blocked senders.
+    pub(crate) fn close(&self) {
+        let mut state = self.state.lock();
+        state.queue.clear();
+        state.closed = true;
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn send(&self, item: T) {
+        let mut state = self.state.lock();
+
+        loop {
+            if state.closed {
+                return;
+            }
+
+            let full = match state.capacity {
+                Some(capacity) => state.queue.len() >= capacity,
+                None => false,
+            };
+            if !full || item.is_control() {
+                break;
+            }
+
+            match state.policy {
+                OverflowPolicy::Block => self.not_full.wait(&mut state),
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    // We never drop control items, if the queue is full of them (only possible
+                    // with a tiny capacity) we drop the new item instead.
+                    match state.queue.iter().position(|queued| !queued.is_control()) {
+                        Some(index) => {
+                            state.queue.remove(index);
+                        }
+                        None => {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::Coalesce => match state.queue.back_mut() {
+                    Some(last) if last.can_coalesce(&item) => {
+                        *last = item;
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    _ => self.not_full.wait(&mut state),
+                },
+            }
+        }
+
+        state.queue.push_back(item);
+        self.not_empty.notify_one();
+    }
+
+    pub(crate) fn recv(&self) -> T {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(item) = state.queue.pop_front() {
+                self.not_full.notify_one();
+                return item;
+            }
+            self.not_empty.wait(&mut state);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! The clock scheduling the events posted at the pace they were received.
+//!
+//! It is shared by [`EventIterator::post_timed`] and the `Player` of the `recording` feature,
+//! which can also pause the clock and move it around.
+//!
+//! [`EventIterator::post_timed`]: crate::hook::event::EventIterator::post_timed
+
+use std::time::{Duration, Instant};
+
+/// The position in a schedule, in milliseconds, scaled by a speed.
+/// The position only advances while the clock runs.
+pub(crate) struct Clock {
+    position: f64,
+    // The time the position was last updated while running, `None` while paused.
+    since: Option<Instant>,
+    speed: f64,
+}
+
+impl Clock {
+    /// A paused clock at the start, running at the recorded speed.
+    pub(crate) fn new() -> Self {
+        Clock {
+            position: 0.0,
+            since: None,
+            speed: 1.0,
+        }
+    }
+
+    /// The current position.
+    pub(crate) fn now(&self) -> f64 {
+        match self.since {
+            Some(since) => self.position + since.elapsed().as_secs_f64() * 1000.0 * self.speed,
+            None => self.position,
+        }
+    }
+
+    /// The position the clock was last set to, paused or resumed at.
+    #[cfg(feature = "recording")]
+    pub(crate) fn position(&self) -> f64 {
+        self.position
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.since.is_some()
+    }
+
+    pub(crate) fn run(&mut self, running: bool) {
+        self.position = self.now();
+        self.since = if running { Some(Instant::now()) } else { None };
+    }
+
+    #[cfg(feature = "recording")]
+    pub(crate) fn set(&mut self, position: f64) {
+        self.position = position;
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    /// # Panics
+    /// Panics if the speed is not a positive number.
+    pub(crate) fn set_speed(&mut self, speed: f64) {
+        assert!(
+            speed > 0.0 && speed.is_finite(),
+            "the speed must be a positive number"
+        );
+        self.run(self.is_running());
+        self.speed = speed;
+    }
+
+    /// How long to wait until the event at `offset` is due, `None` if it is due already.
+    pub(crate) fn until(&self, offset: f64) -> Option<Duration> {
+        let wait = (offset - self.now()) / self.speed;
+        if wait > 0.0 {
+            Some(Duration::from_secs_f64(wait / 1000.0))
+        } else {
+            None
+        }
+    }
+}
@@ -6,7 +6,7 @@ pub type NativeEventKind = ffi::event_type;
 
 bitflags! {
     #[derive(Default)]
-    /// Specifies weather the event is reserved or synthetic.
+    /// Specifies weather the event is reserved, synthetic or repeated.
     ///
     /// `Reserved` events are events that will or did not propagate to the UI of the system, this
     /// flag can be set using the [`reserve_events`] method.
@@ -17,45 +17,163 @@ bitflags! {
     /// when it the event is not synthetic, and could not be set for a synthetic event.
     /// For more information read the [`crate::hook::global`] documentation.
     ///
+    /// `Repeat` events are the repeated [`KeyPressed`] events sent while a key is held down,
+    /// the flag is set by the session from its [`KeyboardState`].
+    ///
     /// [`reserve_events`]: crate::hook::global::reserve_events
+    /// [`KeyPressed`]: crate::hook::event::EventKind::KeyPressed
+    /// [`KeyboardState`]: crate::hook::state::KeyboardState
     pub struct EventMode: u16 {
         const DEFAULT = 0b00000000;
         const RESERVED = 0b00000001;
         const SYNTHETIC = 0b00000010;
+        const REPEAT = 0b00000100;
     }
 }
 
-const MASK_NONE: u32 = 0;
-
-crate::constant_to_enum! {
-    /// Mask applied to events to indicate that they are a part of a sequence or combination
-    /// such as `Ctrl-C`
+bitflags! {
+    #[derive(Default)]
+    /// The modifier keys, mouse buttons and lock keys that were held when the event was generated.
     ///
-    /// Each variant represents the mask that should be applied when creating a sequence with
-    /// the equivalent key, when we want a `Ctrl-A` combination we should apply the `Control` mask
-    /// or one of `LeftControl`, `RightControl` masks if we want to be more specific.
-    (u32 => u16) => EventMask {
-        MASK_NONE => None,
-        MASK_SHIFT_L => LeftShift,
-        MASK_CTRL_L => LeftControl,
-        MASK_META_L => LeftMeta,
-        MASK_ALT_L =>  LeftAlt,
-        MASK_SHIFT_R => RightShift,
-        MASK_CTRL_R => RightControl,
-        MASK_META_R => RightMeta,
-        MASK_ALT_R =>  RightAlt,
-        MASK_SHIFT => Shift,
-        MASK_CTRL => Control,
-        MASK_META => Meta,
-        MASK_ALT => Alt,
-        MASK_BUTTON1 => LeftMouseButton,
-        MASK_BUTTON2 => RightMouseButton,
-        MASK_BUTTON3 => MiddleMouseButton,
-        MASK_BUTTON4 => ExtraMouseButton1,
-        MASK_BUTTON5 => ExtraMouseButton2,
-        MASK_NUM_LOCK => NumLock,
-        MASK_CAPS_LOCK => CapsLock,
-        MASK_SCROLL_LOCK => ScrollLock
+    /// The mask is meant to represent key combinations such as `Ctrl-C`, every key or button in the
+    /// combination sets its own bit, so a mask like `Ctrl+Shift+Button1` contains all three flags.
+    /// The side agnostic flags such as [`SHIFT`] contain both the left and right flags, use
+    /// [`shift`] and friends to check if either side is held.
+    ///
+    /// # Example
+    /// ```rust
+    /// use uiohook_rs::hook::event::Modifiers;
+    ///
+    /// let mask = Modifiers::LEFT_CONTROL | Modifiers::RIGHT_SHIFT | Modifiers::LEFT_MOUSE_BUTTON;
+    /// assert!(mask.ctrl() && mask.shift() && !mask.alt());
+    /// assert!(mask.contains(Modifiers::RIGHT_SHIFT));
+    /// assert!(!mask.contains(Modifiers::SHIFT));
+    /// assert_eq!(mask.buttons(), Modifiers::LEFT_MOUSE_BUTTON);
+    /// ```
+    ///
+    /// [`SHIFT`]: Modifiers::SHIFT
+    /// [`shift`]: Modifiers::shift
+    pub struct Modifiers: u16 {
+        const LEFT_SHIFT = MASK_SHIFT_L as u16;
+        const LEFT_CONTROL = MASK_CTRL_L as u16;
+        const LEFT_META = MASK_META_L as u16;
+        const LEFT_ALT = MASK_ALT_L as u16;
+        const RIGHT_SHIFT = MASK_SHIFT_R as u16;
+        const RIGHT_CONTROL = MASK_CTRL_R as u16;
+        const RIGHT_META = MASK_META_R as u16;
+        const RIGHT_ALT = MASK_ALT_R as u16;
+        const SHIFT = MASK_SHIFT as u16;
+        const CONTROL = MASK_CTRL as u16;
+        const META = MASK_META as u16;
+        const ALT = MASK_ALT as u16;
+        const LEFT_MOUSE_BUTTON = MASK_BUTTON1 as u16;
+        const RIGHT_MOUSE_BUTTON = MASK_BUTTON2 as u16;
+        const MIDDLE_MOUSE_BUTTON = MASK_BUTTON3 as u16;
+        const EXTRA_MOUSE_BUTTON1 = MASK_BUTTON4 as u16;
+        const EXTRA_MOUSE_BUTTON2 = MASK_BUTTON5 as u16;
+        const NUM_LOCK = MASK_NUM_LOCK as u16;
+        const CAPS_LOCK = MASK_CAPS_LOCK as u16;
+        const SCROLL_LOCK = MASK_SCROLL_LOCK as u16;
+    }
+}
+
+impl Modifiers {
+    /// All the modifier keys, on both sides.
+    pub const KEYS: Modifiers = Modifiers::from_bits_truncate(
+        Modifiers::SHIFT.bits
+            | Modifiers::CONTROL.bits
+            | Modifiers::META.bits
+            | Modifiers::ALT.bits,
+    );
+    /// All the mouse buttons.
+    pub const BUTTONS: Modifiers = Modifiers::from_bits_truncate(
+        Modifiers::LEFT_MOUSE_BUTTON.bits
+            | Modifiers::RIGHT_MOUSE_BUTTON.bits
+            | Modifiers::MIDDLE_MOUSE_BUTTON.bits
+            | Modifiers::EXTRA_MOUSE_BUTTON1.bits
+            | Modifiers::EXTRA_MOUSE_BUTTON2.bits,
+    );
+    /// All the lock keys.
+    pub const LOCKS: Modifiers = Modifiers::from_bits_truncate(
+        Modifiers::NUM_LOCK.bits | Modifiers::CAPS_LOCK.bits | Modifiers::SCROLL_LOCK.bits,
+    );
+
+    /// Check if either shift key is held.
+    pub fn shift(&self) -> bool {
+        self.intersects(Modifiers::SHIFT)
+    }
+
+    /// Check if either control key is held.
+    pub fn ctrl(&self) -> bool {
+        self.intersects(Modifiers::CONTROL)
+    }
+
+    /// Check if either meta key is held.
+    pub fn meta(&self) -> bool {
+        self.intersects(Modifiers::META)
+    }
+
+    /// Check if either alt key is held.
+    pub fn alt(&self) -> bool {
+        self.intersects(Modifiers::ALT)
+    }
+
+    /// Only the modifier keys of the mask, see [`KEYS`](Modifiers::KEYS).
+    pub fn keys(&self) -> Modifiers {
+        *self & Modifiers::KEYS
+    }
+
+    /// Only the mouse buttons of the mask, see [`BUTTONS`](Modifiers::BUTTONS).
+    pub fn buttons(&self) -> Modifiers {
+        *self & Modifiers::BUTTONS
+    }
+
+    /// Only the lock keys of the mask, see [`LOCKS`](Modifiers::LOCKS).
+    pub fn locks(&self) -> Modifiers {
+        *self & Modifiers::LOCKS
+    }
+
+    /// The flag set while `key` is held, or locked in the case of the lock keys.
+    /// Returns an empty mask for keys that are not modifiers.
+    pub fn from_key(key: Key) -> Modifiers {
+        match key {
+            Key::LeftShift => Modifiers::LEFT_SHIFT,
+            Key::LeftControl => Modifiers::LEFT_CONTROL,
+            Key::LeftMeta => Modifiers::LEFT_META,
+            Key::LeftAlt => Modifiers::LEFT_ALT,
+            Key::RightShift => Modifiers::RIGHT_SHIFT,
+            Key::RightControl => Modifiers::RIGHT_CONTROL,
+            Key::RightMeta => Modifiers::RIGHT_META,
+            Key::RightAlt => Modifiers::RIGHT_ALT,
+            Key::NumLock => Modifiers::NUM_LOCK,
+            Key::CapsLock => Modifiers::CAPS_LOCK,
+            Key::ScrollLock => Modifiers::SCROLL_LOCK,
+            _ => Modifiers::empty(),
+        }
+    }
+
+    /// The flag set while `button` is held, returns an empty mask for [`MouseButton::NoButton`].
+    pub fn from_button(button: MouseButton) -> Modifiers {
+        match button {
+            MouseButton::Left => Modifiers::LEFT_MOUSE_BUTTON,
+            MouseButton::Right => Modifiers::RIGHT_MOUSE_BUTTON,
+            MouseButton::Middle => Modifiers::MIDDLE_MOUSE_BUTTON,
+            MouseButton::Extra1 => Modifiers::EXTRA_MOUSE_BUTTON1,
+            MouseButton::Extra2 => Modifiers::EXTRA_MOUSE_BUTTON2,
+            _ => Modifiers::empty(),
+        }
+    }
+}
+
+impl From<u16> for Modifiers {
+    fn from(native: u16) -> Self {
+        Modifiers::from_bits_truncate(native)
+    }
+}
+
+impl From<Modifiers> for u16 {
+    fn from(rusty: Modifiers) -> Self {
+        rusty.bits()
     }
 }
 
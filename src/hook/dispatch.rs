@@ -0,0 +1,357 @@
+//! Control how the hooks of a session are called.
+//!
+//! By default every hook is called on the `control thread` of its session, one after the other,
+//! which means that a single slow hook delays all the other hooks, and backs up the event bus.
+//! Each hook can be registered with a [`DispatchMode`] that decides on which thread it is called:
+//!
+//! *   [`Inline`] - The hook is called on the control thread, this is the default and has the lowest
+//!     overhead, it is well suited for hooks that return quickly.
+//!
+//! *   [`Dedicated`] - The hook gets its own worker thread with its own queue of events, so it can
+//!     be as slow as it wants without affecting any other hook.
+//!
+//! *   [`Pool`] - The hook is called on one of the worker threads of a pool shared by all the pooled
+//!     hooks of the session, see [`HookSession::set_pool_size`].
+//!
+//! Regardless of the mode, each hook receives the events in the order they were received by the session.
+//! The queues of the worker threads have the capacity and the overflow policy of the event bus,
+//! see [`HookSession::set_capacity`], the events they drop are counted in [`HookSession::dropped_events`].
+//!
+//! ## Priorities
+//!
+//! Every hook has a priority, hooks with a higher priority are dispatched before hooks with a lower
+//! priority, and hooks with the same priority are dispatched in the order they were registered.
+//! The default priority is 0. An [`Inline`] hook can call [`consume_event`] to mark the event as handled,
+//! in which case the hooks with a lower priority will not receive it. This makes it possible to layer a set
+//! of hooks, for example the shortcuts of a modal overlay, over other hooks.
+//! When a session is stopped, the control thread waits for all the workers to finish handling the events
+//! they received, so once [`HookHandle::wait`] returns, every hook has seen the [`Disabled`] event.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::{Arc, Mutex};
+//! use std::thread::sleep;
+//! use std::time::Duration;
+//!
+//! use uiohook_rs::hook::dispatch::DispatchMode;
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::{hook_start, register_hook, register_hook_with};
+//! use uiohook_rs::{EventKind, HookEvent};
+//!
+//! let keys = Arc::new(Mutex::new(Vec::new()));
+//! let pressed = keys.clone();
+//!
+//! // this hook is slow, but it runs on its own thread so it wont delay the hook below.
+//! register_hook_with(DispatchMode::Dedicated, move |event| {
+//!     sleep(Duration::from_millis(5));
+//!     if let EventKind::KeyPressed(data) = &event.kind {
+//!         pressed.lock().unwrap().push(data.keycode);
+//!     }
+//! });
+//! register_hook(|event| println!("{:?}", event));
+//!
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::A).press().post().unwrap();
+//! HookEvent::keyboard(Key::B).press().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! // once the hook is stopped, the slow hook received all the events in order.
+//! assert_eq!(*keys.lock().unwrap(), vec![Key::A, Key::B]);
+//! ```
+//!
+//! ## Consuming Events
+//!
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! use uiohook_rs::hook::dispatch::{consume_event, HookOptions};
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::{hook_start, register_hook, register_hook_with};
+//! use uiohook_rs::{EventKind, HookEvent};
+//!
+//! static APP: AtomicUsize = AtomicUsize::new(0);
+//!
+//! // the application shortcuts run with the default priority.
+//! register_hook(|event| {
+//!     if let EventKind::KeyPressed(_) = event.kind {
+//!         APP.fetch_add(1, Ordering::SeqCst);
+//!     }
+//! });
+//!
+//! // the overlay handles the escape key before the application gets to see it.
+//! register_hook_with(HookOptions::new().with_priority(10), |event| {
+//!     if let EventKind::KeyPressed(data) = &event.kind {
+//!         if data.keycode == Key::Escape {
+//!             consume_event();
+//!         }
+//!     }
+//! });
+//!
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::Escape).press().post().unwrap();
+//! HookEvent::keyboard(Key::A).press().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! // the application only saw the A key.
+//! assert_eq!(APP.load(Ordering::SeqCst), 1);
+//! ```
+//!
+//! [`Inline`]: DispatchMode::Inline
+//! [`Dedicated`]: DispatchMode::Dedicated
+//! [`Pool`]: DispatchMode::Pool
+//! [`HookSession::set_pool_size`]: crate::hook::session::HookSession::set_pool_size
+//! [`HookSession::set_capacity`]: crate::hook::session::HookSession::set_capacity
+//! [`HookSession::dropped_events`]: crate::hook::session::HookSession::dropped_events
+//! [`HookHandle::wait`]: crate::hook::global::HookHandle::wait
+//! [`Disabled`]: crate::hook::event::EventKind::Disabled
+
+use std::cell::Cell;
+use std::sync::{Arc, Weak};
+use std::thread;
+
+use flume::{bounded, Sender};
+
+use crate::hook::bus::{EventBus, EventQueue, OverflowPolicy, Queued};
+use crate::hook::event::HookEvent;
+use crate::hook::global::HookId;
+use crate::hook::session::SessionInner;
+use crate::hook::state::EventContext;
+
+/// The number of worker threads in the pool of a session, unless configured otherwise.
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+/// On which thread a hook is called, see the [module level documentation](crate::hook::dispatch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchMode {
+    /// Call the hook on the control thread of the session.
+    Inline,
+    /// Call the hook on a worker thread dedicated to this hook.
+    Dedicated,
+    /// Call the hook on a worker thread from the pool of the session.
+    Pool,
+}
+
+impl Default for DispatchMode {
+    fn default() -> Self {
+        DispatchMode::Inline
+    }
+}
+
+/// Options controlling how a hook is dispatched, see [`register_hook_with`].
+///
+/// A [`DispatchMode`] can be used wherever options are expected, in which case the hook will have
+/// the default priority.
+///
+/// [`register_hook_with`]: crate::hook::global::register_hook_with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HookOptions {
+    pub(crate) mode: DispatchMode,
+    pub(crate) priority: i32,
+}
+
+impl HookOptions {
+    /// Create options with the [`Inline`] dispatch mode and a priority of 0.
+    ///
+    /// [`Inline`]: DispatchMode::Inline
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the dispatch mode, see [`DispatchMode`].
+    pub fn with_dispatch(mut self, mode: DispatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the priority, hooks with a higher priority receive the events first.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl From<DispatchMode> for HookOptions {
+    fn from(mode: DispatchMode) -> Self {
+        HookOptions::new().with_dispatch(mode)
+    }
+}
+
+thread_local! {
+    static CONSUMED: Cell<bool> = Cell::new(false);
+}
+
+/// Mark the event currently handled by the calling hook as consumed,
+/// so that hooks with a lower priority will not receive it.
+///
+/// This function only has an effect when called from an [`Inline`] hook, hooks running on worker
+/// threads handle the event while the control thread keeps dispatching it, and so they can not prevent
+/// other hooks from receiving it. Control events such as [`Enabled`] and [`Disabled`] can not be consumed.
+///
+/// [`Inline`]: DispatchMode::Inline
+/// [`Enabled`]: crate::hook::event::EventKind::Enabled
+/// [`Disabled`]: crate::hook::event::EventKind::Disabled
+pub fn consume_event() {
+    CONSUMED.with(|consumed| consumed.set(true));
+}
+
+/// Check if the event was consumed since the last call, and reset the flag.
+pub(crate) fn take_consumed() -> bool {
+    CONSUMED.with(|consumed| consumed.replace(false))
+}
+
+/// What to do with a hook after it panicked, see [`HookSession::set_panic_policy`].
+///
+/// Regardless of the policy, the panic is caught so the other hooks keep running,
+/// and it is reported to the panic handler of the session, see [`HookSession::on_panic`].
+///
+/// [`HookSession::set_panic_policy`]: crate::hook::session::HookSession::set_panic_policy
+/// [`HookSession::on_panic`]: crate::hook::session::HookSession::on_panic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Keep calling the hook for the next events.
+    Keep,
+    /// Stop calling the hook, the hook stays registered and is enabled again if it is re-registered.
+    Disable,
+    /// Unregister the hook.
+    Remove,
+}
+
+impl Default for PanicPolicy {
+    fn default() -> Self {
+        PanicPolicy::Disable
+    }
+}
+
+enum Job {
+    Event(HookId, HookEvent, EventContext),
+    // The worker drops the sender once it handled all the jobs before this one.
+    Flush(Sender<()>),
+    // The worker exits once it handled all the jobs before this one.
+    Exit,
+}
+
+impl Queued for Job {
+    fn is_control(&self) -> bool {
+        match self {
+            Job::Event(_, event, _) => event.is_control(),
+            Job::Flush(_) | Job::Exit => true,
+        }
+    }
+
+    fn can_coalesce(&self, next: &Self) -> bool {
+        match (self, next) {
+            // A pool worker handles the events of several hooks, only the events of the same hook are merged.
+            (Job::Event(id, event, _), Job::Event(next_id, next_event, _)) => {
+                id == next_id && event.can_coalesce(next_event)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The queue of a worker, the events are sent through it without holding on to the worker itself,
+/// since sending might block until the worker makes room.
+#[derive(Clone)]
+pub(crate) struct WorkerQueue(Arc<EventQueue<Job>>);
+
+impl WorkerQueue {
+    pub(crate) fn send(&self, id: HookId, event: HookEvent, context: EventContext) {
+        self.0.send(Job::Event(id, event, context));
+    }
+
+    pub(crate) fn configure(&self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.0.configure(capacity, policy);
+    }
+
+    /// Block until the worker handled all the events sent to it so far,
+    /// returns right away if the worker exited.
+    pub(crate) fn flush(&self) {
+        let (done, wait) = bounded(0);
+        self.0.send(Job::Flush(done));
+        // The worker never sends anything, so this returns once the sender is dropped.
+        let _ = wait.recv();
+    }
+}
+
+pub(crate) struct Worker {
+    queue: WorkerQueue,
+}
+
+impl Worker {
+    /// Spawn a worker whose queue has the capacity and overflow policy of the event bus.
+    pub(crate) fn spawn(session: Weak<SessionInner>, bus: &EventBus) -> Self {
+        let queue = Arc::new(bus.sibling());
+        let receiver = queue.clone();
+        thread::spawn(move || Self::worker_main(session, &receiver));
+        Worker {
+            queue: WorkerQueue(queue),
+        }
+    }
+
+    // The worker holds a weak reference to the session so that the session can be dropped
+    // while it has workers, the worker exits once the session drops it.
+    fn worker_main(session: Weak<SessionInner>, queue: &EventQueue<Job>) {
+        loop {
+            match queue.recv() {
+                Job::Event(id, event, context) => {
+                    if let Some(session) = session.upgrade() {
+                        session.call_hook(id, &event, &context);
+                    }
+                }
+                Job::Flush(done) => drop(done),
+                Job::Exit => {
+                    // Closing the queue drops the jobs sent after this one,
+                    // which releases whoever is flushing the worker.
+                    queue.close();
+                    break;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn queue(&self) -> WorkerQueue {
+        self.queue.clone()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.queue.0.send(Job::Exit);
+    }
+}
+
+pub(crate) struct Pool {
+    workers: Vec<Worker>,
+}
+
+impl Pool {
+    pub(crate) fn new(size: usize, session: Weak<SessionInner>, bus: &EventBus) -> Self {
+        Pool {
+            workers: (0..size.max(1))
+                .map(|_| Worker::spawn(session.clone(), bus))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub(crate) fn queue(&self, id: HookId) -> WorkerQueue {
+        // Each hook is always routed to the same worker, which preserves the order of its events.
+        let index = (id % self.workers.len() as HookId) as usize;
+        self.workers[index].queue()
+    }
+
+    pub(crate) fn queues(&self) -> impl Iterator<Item = WorkerQueue> + '_ {
+        self.workers.iter().map(Worker::queue)
+    }
+
+    pub(crate) fn flush(&self) {
+        for queue in self.queues() {
+            queue.flush();
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! The single native hook shared by all the hook sessions.
+//!
+//! Only one backend can run at a time, so every [`HookSession`] attaches to the driver when it starts
+//! and detaches from it when it stops. The first session to attach starts the backend on a dedicated
+//! `hook thread`, and the last session to detach stops it.
+//!
+//! The driver owns the dispatch procedure installed on the backend, which fans every event out to the
+//! event bus of each attached session. Control events are handled by the driver itself, the `Enabled`
+//! event is forwarded to sessions that attach while the backend is starting, and sessions that attach
+//! while the backend is already running get an `Enabled` event of their own. The `Disabled` event sent by
+//! the backend is swallowed, instead the driver sends a `Disabled` event to a session when it detaches or
+//! when the backend exits, so that the exit status of the backend is always recorded before the session
+//! observes the `Disabled` event.
+//!
+//! [`HookSession`]: crate::hook::session::HookSession
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use parking_lot::{const_mutex, Condvar, Mutex, RwLock};
+
+use crate::error::{HookError, Operation};
+use crate::hook::backend::{Backend, NativeBackend};
+use crate::hook::event::{EventKind, EventMetaData, EventMode, HookEvent};
+use crate::hook::session::SessionInner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DriverStatus {
+    Idle,
+    Starting,
+    Running,
+    Stopping,
+}
+
+struct DriverState {
+    status: DriverStatus,
+    // The session that stopped the backend, it is the one that receives the exit status
+    // of the backend when it was stopped on purpose.
+    shutdown_owner: Option<Arc<SessionInner>>,
+    // Set when the start of the last attached session timed out, the backend is stopped
+    // as soon as it is enabled, unless another session attaches in the meantime.
+    cancelled: bool,
+}
+
+static STATE: (Mutex<DriverState>, Condvar) = (
+    const_mutex(DriverState {
+        status: DriverStatus::Idle,
+        shutdown_owner: None,
+        cancelled: false,
+    }),
+    Condvar::new(),
+);
+
+// The attached sessions are kept separately from the rest of the state because they are read for
+// every event, and we want to keep the dispatch procedure as short as possible.
+static SESSIONS: RwLock<Vec<Arc<SessionInner>>> = parking_lot::const_rwlock(Vec::new());
+
+static BACKEND: Lazy<RwLock<Arc<dyn Backend>>> = Lazy::new(|| RwLock::new(Arc::new(NativeBackend)));
+
+fn control_event(kind: EventKind) -> HookEvent {
+    HookEvent {
+        metadata: EventMetaData::default(),
+        kind,
+    }
+}
+
+pub(crate) fn backend() -> Arc<dyn Backend> {
+    BACKEND.read().clone()
+}
+
+pub(crate) fn set_backend(backend: Arc<dyn Backend>) -> bool {
+    // We hold the state lock while replacing the backend so that no session can start the old backend
+    // in between the check and the replacement.
+    let state = STATE.0.lock();
+    if state.status != DriverStatus::Idle {
+        return false;
+    }
+
+    *BACKEND.write() = backend;
+    true
+}
+
+fn dispatch_event(event: &mut HookEvent) {
+    match event.kind {
+        EventKind::Enabled => {
+            let mut state = STATE.0.lock();
+            if state.status == DriverStatus::Starting {
+                state.status = DriverStatus::Running;
+                if state.cancelled {
+                    state.cancelled = false;
+                    stop_unattended(&mut state);
+                }
+            }
+            for session in SESSIONS.read().iter() {
+                session.send(event.clone());
+            }
+        }
+        // see the module documentation for why this event is not forwarded.
+        EventKind::Disabled => (),
+        _ => {
+            // We don't hold the lock while sending, a session might block until its control thread
+            // makes room for the event, and that control thread might be trying to detach its session.
+            let sessions = SESSIONS.read().clone();
+            if sessions.iter().any(|session| session.reserves(event)) {
+                event.metadata.mode.insert(EventMode::RESERVED);
+            }
+
+            for session in sessions.iter() {
+                session.send(event.clone());
+            }
+        }
+    }
+}
+
+fn hook_thread_main(backend: Arc<dyn Backend>) {
+    // A panicking backend should not leave the attached sessions waiting forever,
+    // so we report the panic like any other error.
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| backend.start())) {
+        Ok(result) => result,
+        Err(panic) => Err(panic.into()),
+    };
+
+    backend.set_dispatch_proc(None);
+
+    let (ref lock, ref cond) = STATE;
+    let mut state = lock.lock();
+    // The backend reports every failure as a failure to start, we can tell whether it was already enabled.
+    let operation = match state.status {
+        DriverStatus::Starting => Operation::Start,
+        _ => Operation::Run,
+    };
+    let result = result.map_err(|err| err.with_operation(operation));
+    state.status = DriverStatus::Idle;
+    state.cancelled = false;
+
+    let sessions = std::mem::take(&mut *SESSIONS.write());
+    match state.shutdown_owner.take() {
+        Some(owner) => {
+            owner.exit(result);
+            owner.send(control_event(EventKind::Disabled));
+        }
+        None => {
+            // The backend exited without being asked to, we let every attached session know.
+            for session in sessions {
+                let session_result = match &result {
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(err.duplicate()),
+                };
+                session.exit(session_result);
+                session.send(control_event(EventKind::Disabled));
+            }
+        }
+    }
+
+    cond.notify_all();
+}
+
+/// Stop the backend without any session waiting for it to exit.
+///
+/// If the backend cannot be stopped it is considered running again, so the next session to attach uses it.
+fn stop_unattended(state: &mut DriverState) {
+    state.status = DriverStatus::Stopping;
+    let backend = backend();
+    thread::spawn(move || {
+        if backend.stop().is_err() {
+            let (ref lock, ref cond) = STATE;
+            let mut state = lock.lock();
+            if state.status == DriverStatus::Stopping {
+                state.status = DriverStatus::Running;
+                cond.notify_all();
+            }
+        }
+    });
+}
+
+/// Attach a session to the driver, starting the backend if it is not running.
+///
+/// The session will receive an `Enabled` event once the backend is ready. If the backend is being stopped
+/// the session is attached once it exits, returns `false` if it did not exit before the deadline.
+pub(crate) fn attach(session: &Arc<SessionInner>, deadline: Option<Instant>) -> bool {
+    let (ref lock, ref cond) = STATE;
+    let mut state = lock.lock();
+
+    // If the backend is being stopped we have to wait for it to exit before we can start it again.
+    while state.status == DriverStatus::Stopping {
+        match deadline {
+            Some(deadline) => {
+                if cond.wait_until(&mut state, deadline).timed_out() {
+                    return false;
+                }
+            }
+            None => cond.wait(&mut state),
+        }
+    }
+
+    SESSIONS.write().push(session.clone());
+
+    match state.status {
+        DriverStatus::Idle => {
+            state.status = DriverStatus::Starting;
+            let backend = backend();
+            backend.set_dispatch_proc(Some(Arc::new(dispatch_event)));
+            thread::spawn(move || hook_thread_main(backend));
+        }
+        DriverStatus::Running => session.send(control_event(EventKind::Enabled)),
+        // the session will receive the `Enabled` event sent by the backend.
+        DriverStatus::Starting => state.cancelled = false,
+        DriverStatus::Stopping => (),
+    }
+    true
+}
+
+/// Detach a session whose start timed out.
+///
+/// Unlike [`detach`] this never waits for the backend, the session receives a `Disabled` event right away.
+/// If no other session is attached the backend is stopped, and if it is still starting, it is stopped as
+/// soon as it is enabled.
+pub(crate) fn cancel(session: &Arc<SessionInner>) {
+    let mut state = STATE.0.lock();
+
+    {
+        let mut sessions = SESSIONS.write();
+        match sessions.iter().position(|s| Arc::ptr_eq(s, session)) {
+            Some(index) => {
+                sessions.remove(index);
+            }
+            // The backend already exited, and the session received a `Disabled` event.
+            None => return,
+        }
+
+        session.send(control_event(EventKind::Disabled));
+        if !sessions.is_empty() {
+            return;
+        }
+    }
+
+    match state.status {
+        DriverStatus::Starting => state.cancelled = true,
+        // The backend was enabled right after the start timed out.
+        DriverStatus::Running => stop_unattended(&mut state),
+        DriverStatus::Idle | DriverStatus::Stopping => (),
+    }
+}
+
+/// Detach a session from the driver, stopping the backend if this was the last attached session.
+///
+/// The session will receive a `Disabled` event once it is fully detached, if the backend was stopped,
+/// the session will also receive the exit status of the backend.
+pub(crate) fn detach(session: &Arc<SessionInner>) -> Result<(), HookError> {
+    let (ref lock, _) = STATE;
+    let mut state = lock.lock();
+
+    {
+        let mut sessions = SESSIONS.write();
+        match sessions.iter().position(|s| Arc::ptr_eq(s, session)) {
+            Some(index) => {
+                sessions.remove(index);
+            }
+            // The session is not attached, either it was never started or the backend already exited.
+            None => return Ok(()),
+        }
+
+        if !sessions.is_empty() {
+            session.send(control_event(EventKind::Disabled));
+            return Ok(());
+        }
+    }
+
+    let previous_status = state.status;
+    state.status = DriverStatus::Stopping;
+    state.shutdown_owner = Some(session.clone());
+    let backend = backend();
+    drop(state);
+
+    // We must not hold the lock while stopping the backend, because some backends might wait for
+    // the event currently being dispatched, which could be waiting on the lock.
+    let res = backend.stop();
+
+    if res.is_err() {
+        let mut state = lock.lock();
+        if state.status == DriverStatus::Stopping {
+            state.status = previous_status;
+            state.shutdown_owner = None;
+            SESSIONS.write().push(session.clone());
+        }
+    }
+
+    res
+}
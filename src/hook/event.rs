@@ -1,19 +1,23 @@
 //! Events types and utilities for working with them.
 
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use ffi::*;
 use uiohook_sys as ffi;
 
+use crate::hook::clock::Clock;
 pub use crate::hook::constants::{
-    EventMask, EventMode, Key, MouseButton, MouseScrollDirection, MouseScrollKind,
+    EventMode, Key, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
 };
 use crate::hook::global::{post_event, postable_event};
-use crate::PostEventError;
+use crate::hook::state::{DEFAULT_REPEAT_DELAY, DEFAULT_REPEAT_INTERVAL};
+use crate::system_properties;
+use crate::HookError;
 
 /// Contains data shared by all event types.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventMetaData {
     /// This field contains a unix time stamp, number of milliseconds since the unix epoch.
     pub time: u128,
@@ -22,8 +26,8 @@ pub struct EventMetaData {
     /// the same mask, to indicate they were pressed at the same time.
     /// Note that not all keys generate these masks, for example Windows will not set the mask if pressing
     /// the A, and D keys at the same time. This only works for "special" keys that can be part of combinations
-    /// like Ctrl, Alt, and mouse buttons.
-    pub mask: EventMask,
+    /// like Ctrl, Alt, and mouse buttons. See [`Modifiers`] for how to query the mask.
+    pub mask: Modifiers,
     /// This field indicates the mode the event is in.
     /// There are two possible modes, and a default.
     /// * [`Reserved`] - can only be set using the [`reserve_events`] function,
@@ -33,12 +37,25 @@ pub struct EventMetaData {
     /// library (specifically the API provided by HookEvent) it will automatically be set when the
     /// event is posted.
     ///
+    /// * [`Repeat`] - cannot be manually set, it is set by the session on the repeated press events
+    /// of a held key, see [`is_repeat`].
+    ///
     /// [`Reserved`]: crate::hook::event::EventMode::RESERVED
     /// [`Synthetic`]: crate::hook::event::EventMode::SYNTHETIC
+    /// [`Repeat`]: crate::hook::event::EventMode::REPEAT
     /// [`reserve_events`]: crate::hook::global::reserve_events
+    /// [`is_repeat`]: EventMetaData::is_repeat
     pub mode: EventMode,
 }
 
+/// The current time in the format of [`EventMetaData::time`], a unix timestamp in milliseconds.
+pub(crate) fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis())
+        .unwrap_or_default()
+}
+
 impl EventMetaData {
     /// Check if the event was created by this library.
     ///
@@ -94,6 +111,50 @@ impl EventMetaData {
     pub fn is_reserved(&self) -> bool {
         self.mode.contains(EventMode::RESERVED)
     }
+
+    /// Check if the event is a press of a key that was already held, which the operating system
+    /// repeats at the [`key_repeat_interval`] after the [`key_repeat_delay`].
+    ///
+    /// The flag is set by the session from its [`KeyboardState`] before the event is dispatched. A press
+    /// of a held key that comes long after the expected repeat is not marked, the release of the key
+    /// was most likely missed, for example because it was released while the session was not running.
+    ///
+    /// [`key_repeat_interval`]: crate::system_properties::key_repeat_interval
+    /// [`key_repeat_delay`]: crate::system_properties::key_repeat_delay
+    /// [`KeyboardState`]: crate::hook::state::KeyboardState
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::hook::global::register_hook;
+    /// use uiohook_rs::{hook_start, EventKind, HookEvent};
+    ///
+    /// static PRESSED: AtomicUsize = AtomicUsize::new(0);
+    /// static REPEATED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// register_hook(|event| {
+    ///     if let EventKind::KeyPressed(_) = event.kind {
+    ///         let counter = if event.is_repeat() { &REPEATED } else { &PRESSED };
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// });
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// for _ in 0..3 {
+    ///     HookEvent::keyboard(Key::A).press().post().unwrap();
+    /// }
+    /// HookEvent::keyboard(Key::A).release().post().unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// assert_eq!(PRESSED.load(Ordering::SeqCst), 1);
+    /// assert_eq!(REPEATED.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn is_repeat(&self) -> bool {
+        self.mode.contains(EventMode::REPEAT)
+    }
 }
 
 crate::map_native! {
@@ -239,14 +300,14 @@ crate::map_native! {
 /// # let handle = hook_start().unwrap();
 /// # unsafe { reserve_events(|e| e.is_synthetic()); }
 ///
-/// use uiohook_rs::hook::event::{EventMask, HookEvent, Key, PairEventIterator};
+/// use uiohook_rs::hook::event::{HookEvent, Key, Modifiers, PairEventIterator};
 /// use uiohook_rs::hook::global::post_event;
 /// # use std::thread::sleep;
 /// # use std::time::Duration;
 ///
 /// let make_events = |k: Key| {
 ///     HookEvent::keyboard(k)
-///         .with_mask(EventMask::LeftControl)
+///         .with_mask(Modifiers::LEFT_CONTROL)
 ///         .pair()
 /// };
 /// let ctrl_c = vec![Key::LeftControl, Key::C];
@@ -290,6 +351,7 @@ crate::map_native! {
 ///     .build();
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HookEvent {
     pub metadata: EventMetaData,
     pub kind: EventKind,
@@ -301,6 +363,7 @@ pub struct HookEvent {
 /// For more information on the events and their data look at the
 /// documentation for the data structs [`KeyboardEvent`], [`MouseEvent`], [`MouseWheelEvent`].
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventKind {
     #[doc(hidden)]
     Enabled,
@@ -318,6 +381,8 @@ pub enum EventKind {
 }
 
 /// A more generic version of [`EventKind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventType {
     Control,
     Keyboard,
@@ -342,6 +407,28 @@ impl HookEvent {
         }
     }
 
+    /// Create the [`KeyTyped`] events that type `text`, one event per UTF-16 code unit like the
+    /// operating system reports them, so characters outside the basic multilingual plane are split into
+    /// two events holding a surrogate pair.
+    ///
+    /// [`KeyTyped`]: EventKind::KeyTyped
+    pub fn typed(text: &str) -> Vec<HookEvent> {
+        let meta = EventMetaData {
+            mode: EventMode::SYNTHETIC,
+            ..Default::default()
+        };
+        text.encode_utf16()
+            .map(|keychar| HookEvent {
+                metadata: meta.clone(),
+                kind: EventKind::KeyTyped(KeyboardEvent {
+                    keycode: Key::Undefined,
+                    rawcode: 0,
+                    keychar,
+                }),
+            })
+            .collect()
+    }
+
     /// Start creating a mouse event that will affect the specified `button`.
     pub fn mouse(button: MouseButton) -> MouseEventBuilder {
         let meta = EventMetaData {
@@ -389,6 +476,11 @@ impl HookEvent {
         self.metadata.is_reserved()
     }
 
+    /// Wrapper around [`EventMetaData::is_repeat`].
+    pub fn is_repeat(&self) -> bool {
+        self.metadata.is_repeat()
+    }
+
     /// Get a more generic event type then the one provided by [`EventKind`].
     ///
     /// # Example
@@ -459,7 +551,7 @@ impl HookEvent {
     ///
     /// handle.stop();
     /// ```
-    pub fn post(self) -> Result<(), PostEventError> {
+    pub fn post(self) -> Result<(), HookError> {
         post_event(self)
     }
 }
@@ -497,14 +589,14 @@ impl EventPair {
     // We use this method to make sure both events are postable before posting.
     // The check performed in `post_event` is not enough because we dont want to post the first event
     // only to find out the second is not postable.
-    pub(crate) fn postable(&self) -> Result<(), PostEventError> {
+    pub(crate) fn postable(&self) -> Result<(), HookError> {
         postable_event(&self.press)?;
         postable_event(&self.release)
     }
 
     /// post both the press and release events one after the other press -> release,
     /// with no delay between them.
-    pub fn post(self) -> Result<(), PostEventError> {
+    pub fn post(self) -> Result<(), HookError> {
         self.postable()?;
         post_event(self.press)?;
         post_event(self.release)
@@ -532,7 +624,7 @@ impl EventPair {
     /// println!("done!");
     /// # hook_stop().unwrap();
     /// ```
-    pub fn post_delayed(self, delay: Duration) -> Result<(), PostEventError> {
+    pub fn post_delayed(self, delay: Duration) -> Result<(), HookError> {
         self.postable()?;
 
         post_event(self.press)?;
@@ -570,7 +662,7 @@ impl EventPair {
     /// // two milliseconds later the release event will be registered.
     /// # hook_stop().unwrap();
     /// ```
-    pub fn post_delayed_async(self, delay: Duration) -> Result<(), PostEventError> {
+    pub fn post_delayed_async(self, delay: Duration) -> Result<(), HookError> {
         self.postable()?;
 
         let (press, release) = self.into();
@@ -595,7 +687,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// event is posted, then immediately the release, and only then the next pair is is posted.
     ///
     /// This method simply consumes the iterator calling [`EventPair::post`] on each pair.
-    fn post(self) -> Result<(), PostEventError> {
+    fn post(self) -> Result<(), HookError> {
         for ep in self {
             ep.post()?;
         }
@@ -611,7 +703,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// all events have been posted
     ///
     /// [`post`]: PairEventIterator::post
-    fn post_delayed(self, delay: Duration) -> Result<(), PostEventError> {
+    fn post_delayed(self, delay: Duration) -> Result<(), HookError> {
         for ep in self {
             ep.post_delayed(delay)?;
         }
@@ -630,7 +722,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// is spawned and it the iterator is consumed normally.
     ///
     /// [`post_delayed`]: PairEventIterator::post_delayed
-    fn post_delayed_async(self, delay: Duration) -> Result<(), PostEventError> {
+    fn post_delayed_async(self, delay: Duration) -> Result<(), HookError> {
         let events: Vec<EventPair> = self.collect();
         for ep in events.iter() {
             ep.postable()?;
@@ -658,13 +750,13 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// # let handle = hook_start().unwrap();
     /// # unsafe { reserve_events(|e| e.is_synthetic()); }
     /// # use std::time::Duration;
-    /// use uiohook_rs::hook::event::{EventMask, Key, PairEventIterator};
+    /// use uiohook_rs::hook::event::{Key, Modifiers, PairEventIterator};
     /// use uiohook_rs::HookEvent;
     ///
     /// // first we create an iterator of keyboard events.
     /// let sequence = vec![Key::LeftControl, Key::C].into_iter().map(|k| {
     ///     HookEvent::keyboard(k)
-    ///         .with_mask(EventMask::LeftControl)
+    ///         .with_mask(Modifiers::LEFT_CONTROL)
     ///         .pair()
     /// });
     ///
@@ -676,7 +768,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     ///
     /// # hook_stop().unwrap();
     /// ```
-    fn post_sequence(self) -> Result<(), PostEventError> {
+    fn post_sequence(self) -> Result<(), HookError> {
         let mut pres_vec = Vec::new();
         let mut release_vec = Vec::new();
 
@@ -712,13 +804,13 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// # let handle = hook_start().unwrap();
     /// # unsafe { reserve_events(|e| e.is_synthetic()); }
     /// # use std::time::Duration;
-    /// use uiohook_rs::hook::event::{EventMask, Key, PairEventIterator};
+    /// use uiohook_rs::hook::event::{Key, Modifiers, PairEventIterator};
     /// use uiohook_rs::HookEvent;
     ///
     /// // first we create an iterator of keyboard events.
     /// let sequence = vec![Key::LeftControl, Key::C].into_iter().map(|k| {
     ///     HookEvent::keyboard(k)
-    ///         .with_mask(EventMask::LeftControl)
+    ///         .with_mask(Modifiers::LEFT_CONTROL)
     ///         .pair()
     /// });
     ///
@@ -730,7 +822,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     ///
     /// # hook_stop().unwrap();
     /// ```
-    fn post_delayed_sequence(self, delay: Duration) -> Result<(), PostEventError> {
+    fn post_delayed_sequence(self, delay: Duration) -> Result<(), HookError> {
         let mut pres_vec = Vec::new();
         let mut release_vec = Vec::new();
 
@@ -767,13 +859,13 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     /// # let handle = hook_start().unwrap();
     /// # unsafe { reserve_events(|e| e.is_synthetic()); }
     /// # use std::time::Duration;
-    /// use uiohook_rs::hook::event::{EventMask, Key, PairEventIterator};
+    /// use uiohook_rs::hook::event::{Key, Modifiers, PairEventIterator};
     /// use uiohook_rs::HookEvent;
     ///
     /// // first we create an iterator of keyboard events.
     /// let sequence = vec![Key::LeftControl, Key::C].into_iter().map(|k| {
     ///     HookEvent::keyboard(k)
-    ///         .with_mask(EventMask::LeftControl)
+    ///         .with_mask(Modifiers::LEFT_CONTROL)
     ///         .pair()
     /// });
     ///
@@ -787,7 +879,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
     ///
     /// # hook_stop().unwrap();
     /// ```
-    fn post_delayed_async_sequence(self, delay: Duration) -> Result<(), PostEventError> {
+    fn post_delayed_async_sequence(self, delay: Duration) -> Result<(), HookError> {
         let mut pres_vec = Vec::new();
         let mut release_vec = Vec::new();
 
@@ -814,7 +906,7 @@ pub trait PairEventIterator: Iterator<Item = EventPair> + Sized {
 impl<T> PairEventIterator for T where T: Iterator<Item = EventPair> {}
 
 pub trait EventIterator: Iterator<Item = HookEvent> + Sized {
-    fn post(self) -> Result<(), PostEventError> {
+    fn post(self) -> Result<(), HookError> {
         for e in self {
             e.post()?;
         }
@@ -822,7 +914,7 @@ pub trait EventIterator: Iterator<Item = HookEvent> + Sized {
         Ok(())
     }
 
-    fn post_delayed(self, delay: Duration) -> Result<(), PostEventError> {
+    fn post_delayed(self, delay: Duration) -> Result<(), HookError> {
         for e in self {
             e.post()?;
             sleep(delay);
@@ -831,7 +923,60 @@ pub trait EventIterator: Iterator<Item = HookEvent> + Sized {
         Ok(())
     }
 
-    fn post_delayed_async(self, delay: Duration) -> Result<(), PostEventError> {
+    /// Post the events at the pace they were received, according to the time stamps in their metadata,
+    /// blocking until the last one is posted. The events are replayed `speed` times faster than they
+    /// were received, and events without a time stamp are posted right away.
+    ///
+    /// Unlike [`post_delayed`](EventIterator::post_delayed) the time it takes to post an event does not add
+    /// up, since every event is scheduled relative to the first one. See the `Player` of the `recording`
+    /// feature for a scheduler that can also be paused and moved around.
+    ///
+    /// # Panics
+    /// Panics if the speed is not a positive number.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use uiohook_rs::hook::event::{EventIterator, Key};
+    /// use uiohook_rs::testing::VirtualDevice;
+    /// use uiohook_rs::{EventKind, HookEvent};
+    ///
+    /// let device = VirtualDevice::new().with_loopback();
+    /// device.install();
+    ///
+    /// let (mut press, mut release) = HookEvent::keyboard(Key::A).pair().into();
+    /// press.metadata.time = 1000;
+    /// release.metadata.time = 1300;
+    ///
+    /// let started = Instant::now();
+    /// vec![press, release].into_iter().post_timed(3.0).unwrap();
+    /// assert!(started.elapsed() >= Duration::from_millis(100));
+    ///
+    /// let posted = device.posted();
+    /// assert_eq!(posted.len(), 2);
+    /// assert!(matches!(posted[0].kind, EventKind::KeyPressed(_)));
+    /// assert!(matches!(posted[1].kind, EventKind::KeyReleased(_)));
+    /// ```
+    fn post_timed(self, speed: f64) -> Result<(), HookError> {
+        let mut clock = Clock::new();
+        clock.set_speed(speed);
+        clock.run(true);
+        let mut first = None;
+        for e in self {
+            if e.metadata.time != 0 {
+                let first = *first.get_or_insert(e.metadata.time);
+                if let Some(wait) = clock.until(e.metadata.time.saturating_sub(first) as f64) {
+                    sleep(wait);
+                }
+            }
+            e.post()?;
+        }
+
+        Ok(())
+    }
+
+    fn post_delayed_async(self, delay: Duration) -> Result<(), HookError> {
         let mut res = Ok(());
         let mut postable = Vec::new();
         for e in self {
@@ -863,7 +1008,7 @@ pub struct KeyboardEventBuilder {
     event: KeyboardEvent,
 }
 impl KeyboardEventBuilder {
-    pub fn with_mask(mut self, mask: EventMask) -> Self {
+    pub fn with_mask(mut self, mask: Modifiers) -> Self {
         self.meta.mask = mask;
         self
     }
@@ -894,6 +1039,71 @@ impl KeyboardEventBuilder {
             kind: EventKind::KeyReleased(self.event),
         }
     }
+
+    /// Hold the key for `duration`, like a user would, blocking until it is released.
+    ///
+    /// The press event is repeated at the [`key_repeat_interval`] once the [`key_repeat_delay`]
+    /// elapsed, which default to 33 and 500 milliseconds when the system does not report them.
+    /// The release event is posted even if one of the repeated press events fails to be posted.
+    ///
+    /// [`key_repeat_interval`]: crate::system_properties::key_repeat_interval
+    /// [`key_repeat_delay`]: crate::system_properties::key_repeat_delay
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::time::Duration;
+    ///
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::hook::global::register_hook;
+    /// use uiohook_rs::{hook_start, EventKind, HookEvent};
+    ///
+    /// static REPEATED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// register_hook(|event| {
+    ///     if let EventKind::KeyPressed(_) = event.kind {
+    ///         if event.is_repeat() {
+    ///             REPEATED.fetch_add(1, Ordering::SeqCst);
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// HookEvent::keyboard(Key::Space)
+    ///     .hold(Duration::from_secs(1))
+    ///     .unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// // the key is repeated once the delay elapsed.
+    /// assert!(REPEATED.load(Ordering::SeqCst) > 0);
+    /// ```
+    pub fn hold(self, duration: Duration) -> Result<(), HookError> {
+        let delay = system_properties::key_repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY);
+        let interval = system_properties::key_repeat_interval().unwrap_or(DEFAULT_REPEAT_INTERVAL);
+
+        let press = HookEvent {
+            metadata: self.meta.clone(),
+            kind: EventKind::KeyPressed(self.event.clone()),
+        };
+        let start = Instant::now();
+        press.clone().post()?;
+
+        let mut repeated = Ok(());
+        let mut next = delay;
+        while next < duration {
+            sleep((start + next).saturating_duration_since(Instant::now()));
+            repeated = press.clone().post();
+            if repeated.is_err() {
+                break;
+            }
+            next += interval;
+        }
+
+        sleep((start + duration).saturating_duration_since(Instant::now()));
+        let released = self.release().post();
+        repeated.and(released)
+    }
 }
 
 pub struct MouseEventBuilder {
@@ -906,7 +1116,14 @@ impl MouseEventBuilder {
         self
     }
 
-    pub fn with_mask(mut self, mask: EventMask) -> Self {
+    /// Set the position of the cursor for press and release events, which are placed at the origin by default.
+    pub fn with_position(mut self, x: i16, y: i16) -> Self {
+        self.event.x = x;
+        self.event.y = y;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Modifiers) -> Self {
         self.meta.mask = mask;
         self
     }
@@ -1019,7 +1236,7 @@ impl MouseWheelEventBuilder {
         self
     }
 
-    pub fn with_mask(mut self, mask: EventMask) -> Self {
+    pub fn with_mask(mut self, mask: Modifiers) -> Self {
         self.meta.mask = mask;
         self
     }
@@ -27,6 +27,15 @@
 //!     a different thread (like UI or external event), or it can be called from the [`hook_start`] when some
 //!     key or mouse button is pressed.
 //!
+//! Note that neither thread calls into libuiohook directly, both run against the installed
+//! [`Backend`], which defaults to [`NativeBackend`] and can be replaced with [`set_backend`].
+//! The examples in this module install a [`VirtualDevice`] so they can run without a display,
+//! see the [`testing`] module for more information.
+//!
+//! All the functions in this module operate on the default [`HookSession`], returned by
+//! [`HookSession::global`]. Libraries that need hooks which can be started and stopped independently
+//! of the rest of the process should create their own session instead, see the [`session`] module.
+//!
 //! ## Posting Events
 //!
 //! Posting events is relatively straight forward, for the most part what happens is the user posts
@@ -55,6 +64,7 @@
 //! ## Creating Hooks
 //!
 //! ```
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
 //! use uiohook_rs::hook::event::{EventKind, HookEvent};
 //! use uiohook_rs::hook::global::{hook_start, hook_stop, register_hook};
 //! # use std::thread::sleep;
@@ -75,6 +85,7 @@
 //! ## Removing Hooks
 //!
 //! ```
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
 //! use uiohook_rs::hook::event::{EventKind, HookEvent};
 //! use uiohook_rs::hook::global::{hook_start, hook_stop, register_hook, unregister_hook};
 //! # use std::thread::sleep;
@@ -97,6 +108,7 @@
 //! ## Using Start Blocking
 //!
 //! ```
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
 //! # use uiohook_rs::hook::global::reserve_events;
 //! # // prevent these events from effecting the user when running tests
 //! # unsafe {
@@ -131,392 +143,81 @@
 //! ```
 //!
 //! [`Hook`]: crate::hook::Hook
+//! [`Backend`]: crate::hook::backend::Backend
+//! [`NativeBackend`]: crate::hook::backend::NativeBackend
+//! [`VirtualDevice`]: crate::testing::VirtualDevice
+//! [`testing`]: crate::testing
+//! [`HookSession`]: crate::hook::session::HookSession
+//! [`HookSession::global`]: crate::hook::session::HookSession::global
+//! [`session`]: crate::hook::session
 
-// we only use DerefMut on windows.
-#[allow(unused_imports)]
-use std::ops::DerefMut;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::thread::{self, JoinHandle};
-
-use dashmap::DashMap;
-use flume::{unbounded, Receiver, Sender};
-use once_cell::sync::Lazy;
-use parking_lot::{const_mutex, Condvar, Mutex};
-
-use crate::error::{HookError, PostEventError};
-use crate::hook::event::{EventKind, EventMetaData, HookEvent};
-
-type HookCallback = Box<dyn Fn(&HookEvent) + Sync + Send>;
-type HookFilter = Box<dyn Fn(&HookEvent) -> bool + Sync + Send>;
-
-static RUNNING: AtomicBool = AtomicBool::new(false);
-static ENABLED: (Mutex<bool>, Condvar) = (const_mutex(false), Condvar::new());
-
-static EVENT_BUS: Lazy<(Sender<HookEvent>, Receiver<HookEvent>)> = Lazy::new(unbounded);
-static HOOKS: Lazy<Arc<DashMap<HookId, HookCallback, ahash::RandomState>>> =
-    Lazy::new(|| Arc::new(DashMap::with_hasher(ahash::RandomState::new())));
-
-static RESERVE_CALLBACK: Mutex<Option<HookFilter>> = const_mutex(None);
-
-mod native {
-    // Cstr is only used in the logger function that is compiled only when the logging
-    // feature is enables.
-    #[allow(unused_imports)]
-    use std::ffi::CStr;
-    use std::sync::atomic::{AtomicU32, Ordering};
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    use ffi::uiohook_event;
-    use once_cell::sync::OnceCell;
-    use parking_lot::{const_mutex, Mutex};
-    use uiohook_sys as ffi;
-
-    use crate::hook::constants::*;
-    use crate::hook::event::{
-        EventKind, EventMetaData, HookEvent, KeyboardEvent, MouseEvent, MouseWheelEvent,
-    };
-    use crate::hook::global::RESERVE_CALLBACK;
-    use crate::HookError;
-
-    static BASE_TIMESTAMP: OnceCell<u128> = OnceCell::new();
-    static SYNTHETIC: AtomicU32 = AtomicU32::new(0);
-
-    fn set_timestamp(metadata: &mut EventMetaData) {
-        // libuiohook uses the system uptime as a timestamp, which means that if we shut down the computer,
-        // and then run the library again the timestamp could be smaller than one acquired before shutting
-        // down the computer. We don't want to make the system call for getting the current time on every call,
-        // especially because we already have a timestamp. What we do here is calculate the difference
-        // between unix time stamp and the system uptime, giving us the unix timestamp of the system startup
-        // and in later calls all we need to do is add the system timestamp to the base time we calculated.
-        metadata.time = BASE_TIMESTAMP.get_or_init(|| {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("SystemTime before unix epoch, what sorcery is this ?")
-                .as_millis()
-                - metadata.time as u128
-        }) + metadata.time as u128;
-    }
-
-    fn set_mode(rusty_event: &mut HookEvent, native_event: &mut ffi::uiohook_event) {
-        // we only need this mut on windows to possibly change the type in the following if.
-        #[allow(unused_mut)]
-        let mut event_type = native_event.type_;
-
-        // This is used to make it possible to create synthetic drag events on windows.
-        // Because on windows we cant actually create a drag event,
-        // we need to do a press -> move -> release when the event sent by the OS will be a drag event
-        // but the SYNTHETIC atomic will have the move type.
-        // So on windows we do not distinguish between drag and move events in regard to weather
-        // they are synthetic.
-        #[cfg(target_os = "windows")]
-        if event_type == NativeEventKind::EVENT_MOUSE_DRAGGED {
-            event_type = NativeEventKind::EVENT_MOUSE_MOVED;
-        }
-
-        if SYNTHETIC
-            .compare_exchange(event_type as u32, 0, Ordering::SeqCst, Ordering::SeqCst)
-            .is_ok()
-        {
-            rusty_event.metadata.mode.insert(EventMode::SYNTHETIC);
-        }
-
-        if let Some(callback) = &*RESERVE_CALLBACK.lock() {
-            if callback(rusty_event) {
-                rusty_event.metadata.mode.insert(EventMode::RESERVED);
-                native_event.reserved = EventMode::RESERVED.bits();
-            }
-        }
-    }
-
-    fn from_native(native: &ffi::uiohook_event) -> HookEvent {
-        let mut meta = EventMetaData {
-            time: native.time as u128,
-            mask: native.mask.into(),
-            mode: EventMode::from_bits(native.reserved).unwrap_or(EventMode::DEFAULT),
-        };
-
-        set_timestamp(&mut meta);
-
-        #[inline(always)]
-        fn from_keyboard(native: &ffi::uiohook_event) -> KeyboardEvent {
-            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
-            unsafe { &native.data.keyboard }.into()
-        }
-
-        #[inline(always)]
-        fn from_mouse(native: &ffi::uiohook_event) -> MouseEvent {
-            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
-            unsafe { &native.data.mouse }.into()
-        }
-
-        #[inline(always)]
-        fn from_mouse_wheel(native: &ffi::uiohook_event) -> MouseWheelEvent {
-            // SAFETY: we assume that the native library sets the union to the type matching what is found in `native.type_`.
-            unsafe { &native.data.wheel }.into()
-        }
-
-        let event_data = match native.type_ {
-            NativeEventKind::EVENT_HOOK_ENABLED => EventKind::Enabled,
-            NativeEventKind::EVENT_HOOK_DISABLED => EventKind::Disabled,
-            NativeEventKind::EVENT_KEY_TYPED => EventKind::KeyTyped(from_keyboard(native)),
-            NativeEventKind::EVENT_KEY_PRESSED => EventKind::KeyPressed(from_keyboard(native)),
-            NativeEventKind::EVENT_KEY_RELEASED => EventKind::KeyReleased(from_keyboard(native)),
-            NativeEventKind::EVENT_MOUSE_CLICKED => EventKind::MouseClicked(from_mouse(native)),
-            NativeEventKind::EVENT_MOUSE_PRESSED => EventKind::MousePressed(from_mouse(native)),
-            NativeEventKind::EVENT_MOUSE_RELEASED => EventKind::MouseReleased(from_mouse(native)),
-            NativeEventKind::EVENT_MOUSE_MOVED => EventKind::MouseMoved(from_mouse(native)),
-            NativeEventKind::EVENT_MOUSE_DRAGGED => EventKind::MouseDragged(from_mouse(native)),
-            NativeEventKind::EVENT_MOUSE_WHEEL => EventKind::MouseWheel(from_mouse_wheel(native)),
-        };
-
-        HookEvent {
-            metadata: meta,
-            kind: event_data,
-        }
-    }
-
-    fn into_native(event: HookEvent) -> ffi::uiohook_event {
-        let mask = event.metadata.mask;
-
-        let (event_type, event_data) = match event.kind {
-            EventKind::Enabled => (
-                NativeEventKind::EVENT_HOOK_ENABLED,
-                ffi::_uiohook_event__bindgen_ty_1::default(),
-            ),
-            EventKind::Disabled => (
-                NativeEventKind::EVENT_HOOK_DISABLED,
-                ffi::_uiohook_event__bindgen_ty_1::default(),
-            ),
-            EventKind::KeyTyped(event_data) => (
-                NativeEventKind::EVENT_KEY_TYPED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    keyboard: event_data.into(),
-                },
-            ),
-            EventKind::KeyPressed(event_data) => (
-                NativeEventKind::EVENT_KEY_PRESSED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    keyboard: event_data.into(),
-                },
-            ),
-            EventKind::KeyReleased(event_data) => (
-                NativeEventKind::EVENT_KEY_RELEASED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    keyboard: event_data.into(),
-                },
-            ),
-            EventKind::MouseClicked(event_data) => (
-                NativeEventKind::EVENT_MOUSE_CLICKED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    mouse: event_data.into(),
-                },
-            ),
-            EventKind::MousePressed(event_data) => (
-                NativeEventKind::EVENT_MOUSE_PRESSED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    mouse: event_data.into(),
-                },
-            ),
-            EventKind::MouseReleased(event_data) => (
-                NativeEventKind::EVENT_MOUSE_RELEASED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    mouse: event_data.into(),
-                },
-            ),
-            EventKind::MouseMoved(event_data) => (
-                NativeEventKind::EVENT_MOUSE_MOVED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    mouse: event_data.into(),
-                },
-            ),
-            EventKind::MouseDragged(event_data) => (
-                NativeEventKind::EVENT_MOUSE_DRAGGED,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    mouse: event_data.into(),
-                },
-            ),
-            EventKind::MouseWheel(event_data) => (
-                NativeEventKind::EVENT_MOUSE_WHEEL,
-                ffi::_uiohook_event__bindgen_ty_1 {
-                    wheel: event_data.into(),
-                },
-            ),
-        };
-
-        ffi::uiohook_event {
-            type_: event_type,
-            data: event_data,
-            // we dont need to set the meta data here, since it will is ignored when the event is posted,
-            // and the OS will create its own meta data.
-            time: 0,
-            mask: mask.into(),
-            reserved: 0,
-        }
-    }
-
-    extern "C" fn event_handler(event: *mut ffi::uiohook_event) {
-        let (sender, _) = &*super::EVENT_BUS;
-
-        // SAFETY: We assume that if the pointer is pointing to a valid uiohook_event,
-        // as specified by the native library. Beyond that we only use the original event data
-        // in order to construct our own representation, once the HookEvent struct is created it will
-        // be used and the original pointer and data will not be read or mutated.
-        // This means that the pointer can be safely freed when this function is complete.
-        if let Some(mut native_event) = unsafe { event.as_mut() } {
-            let mut rusty_event = from_native(native_event);
-            set_mode(&mut rusty_event, &mut native_event);
-
-            // We can ignore the send error here because our receiver is static and will not
-            // be dropped until the end of the program.
-            let _ = sender.send(rusty_event);
-        }
-    }
-
-    #[cfg(feature = "logging")]
-    extern "C" fn logger(level: ffi::log_level, raw_message: *const std::os::raw::c_char) -> bool {
-        match unsafe { CStr::from_ptr(raw_message) }.to_str() {
-            Ok(log_message) => match level {
-                ffi::log_level::LOG_LEVEL_INFO => log::info!("{}", log_message),
-                ffi::log_level::LOG_LEVEL_DEBUG => log::debug!("{}", log_message),
-                ffi::log_level::LOG_LEVEL_WARN => log::warn!("{}", log_message),
-                ffi::log_level::LOG_LEVEL_ERROR => log::error!("{}", log_message),
-            },
-            Err(_) => return false,
-        }
-
-        return true;
-    }
-
-    #[cfg(feature = "logging")]
-    pub fn enable_logging() {
-        unsafe { ffi::hook_set_rusty_logger(Some(logger)) }
-    }
-
-    pub fn set_event_handler() {
-        unsafe { ffi::hook_set_dispatch_proc(Some(event_handler)) }
-    }
-
-    pub fn remove_event_handler() {
-        unsafe { ffi::hook_set_dispatch_proc(None) }
-    }
-
-    pub fn post_event(event: HookEvent) {
-        static POST_MUTEX: Mutex<()> = const_mutex(());
-
-        let mut native_event = into_native(event);
-        let _guard = POST_MUTEX.lock();
-        SYNTHETIC.store(native_event.type_ as u32, Ordering::SeqCst);
-        unsafe {
-            ffi::hook_post_event(&mut native_event as *mut uiohook_event);
-        };
-    }
-
-    pub fn hook_start() -> Result<(), HookError> {
-        match unsafe { ffi::hook_run() as u32 } {
-            ffi::UIOHOOK_SUCCESS => Ok(()),
-            status => Err(status.into()),
-        }
-    }
-
-    pub fn hook_stop() -> Result<(), HookError> {
-        match unsafe { ffi::hook_stop() as u32 } {
-            ffi::UIOHOOK_SUCCESS => Ok(()),
-            status => Err(status.into()),
-        }
-    }
-}
-
-fn control_thread_main() -> JoinHandle<Result<(), HookError>> {
-    #[cfg(feature = "logging")]
-    native::enable_logging();
-    native::set_event_handler();
-    let hook_thread = thread::spawn(hook_thread_main);
-    let (_, receiver) = &*EVENT_BUS;
-
-    while let Ok(event) = receiver.recv() {
-        if let EventKind::Enabled = &event.kind {
-            // When we receive the enabled event from the OS we notify the conditional variable so
-            // that the start function can complete.
-            let (ref lock, ref cond) = ENABLED;
-            let mut ready = lock.lock();
-            *ready = true;
-            cond.notify_all();
-        }
-
-        for hook in HOOKS.iter() {
-            hook.value()(&event)
-        }
-
-        // If the event we received was of the hook being disabled
-        // we can stop listening to the hook events.
-        // After breaking out of the listening loop the control thread will
-        // complete.
-        if let EventKind::Disabled = event.kind {
-            break;
-        }
-    }
-
-    native::remove_event_handler();
-    RUNNING.store(false, Ordering::SeqCst);
-    hook_thread
-}
-
-fn hook_thread_main() -> Result<(), HookError> {
-    // We need to send the Disabled event here in case the control thread has
-    // started the event loop and is waiting for an event. If we don't send a
-    // Disabled event the thread will wait indecently.
-    if let Err(err) = native::hook_start() {
-        let (sender, _) = &*EVENT_BUS;
-
-        // We don't care about the error here because our channel is static, if the receiver is dropped
-        // it means that the process is exiting anyway, and the control thread loop will be broken.
-        let _ = sender.send(HookEvent {
-            metadata: EventMetaData::default(),
-            kind: EventKind::Disabled,
-        });
-        return Err(err);
-    }
-
-    Ok(())
-}
-
-/// Handle for the control and hook threads, used when starting the hook in non blocking "mode".
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use parking_lot::{const_mutex, Mutex};
+
+use crate::error::HookError;
+use crate::hook::backend::Backend;
+use crate::hook::dispatch::HookOptions;
+use crate::hook::driver;
+#[cfg(feature = "async")]
+use crate::hook::event::EventType;
+use crate::hook::event::{EventKind, HookEvent};
+use crate::hook::session::{HookCallback, HookSession};
+use crate::hook::state::{KeyboardState, MouseState};
+use crate::hook::stats::HookStats;
+#[cfg(feature = "async")]
+use crate::hook::stream::EventStream;
+
+/// Handle for the control thread of a session, used when starting the hook in non blocking "mode".
 ///
 /// This is simply a wrapper type around [`std::thread::JoinHandle<T>`] that is simpler to use
 /// in this case.
 pub struct HookHandle {
-    handle: JoinHandle<JoinHandle<Result<(), HookError>>>,
+    session: HookSession,
+    handle: JoinHandle<Result<(), HookError>>,
+    #[cfg(feature = "async")]
+    done: flume::Receiver<()>,
 }
 
 impl HookHandle {
-    fn from_raw(handle: JoinHandle<JoinHandle<Result<(), HookError>>>) -> Self {
-        HookHandle { handle }
+    pub(crate) fn new(
+        session: HookSession,
+        handle: JoinHandle<Result<(), HookError>>,
+        #[cfg(feature = "async")] done: flume::Receiver<()>,
+    ) -> Self {
+        HookHandle {
+            session,
+            handle,
+            #[cfg(feature = "async")]
+            done,
+        }
     }
 
-    /// Wait for both the control and hook threads to complete.
+    /// Wait for the control thread of the session to complete.
     ///
     /// This method will return on one of the following conditions:
-    /// 1. The `control thread` has panicked for some reason. (you get the appropriate [`HookError::Unknown`])
-    /// 2. The `hook thread` panicked for some reason. (you get the appropriate [`HookError::Unknown`])
+    /// 1. The `control thread` has panicked for some reason. (you get a [`HookError`] of kind [`ErrorKind::Panic`])
+    /// 2. The `hook thread` panicked for some reason. (you get a [`HookError`] of kind [`ErrorKind::Panic`])
     /// 3. There was an error when starting, or stopping the hook thread. (you get the appropriate [`HookError`])
     /// 4. The hook thread stopped, then the control thread stopped. (you get `Ok(())`)
     ///
     /// Note that if one of the threads panics the panic is included in the HookError,
     /// meaning that it is possible to continue the panicked into the thread that called wait
-    /// using [`std::panic::resume_unwind`]
+    /// using [`std::panic::resume_unwind`], see [`HookError::into_panic`].
+    ///
+    /// A panic inside a hook does not unwind the control thread, it is caught and reported to the panic handler
+    /// of the session instead, see [`HookSession::on_panic`]. The handler can continue the panic on the
+    /// control thread, which stops the session and lets the panic be observed here.
     ///
     /// # Example
     /// ```should_panic
-    /// # use uiohook_rs::hook::global::{reserve_events, post_event};
-    /// # // prevent these events from effecting the user when running tests
-    /// # unsafe { reserve_events(|e| e.is_synthetic()); }
-    ///
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
     /// use uiohook_rs::hook::event::{EventKind, HookEvent, MouseButton};
     /// use uiohook_rs::hook::global::{hook_start, hook_stop, register_hook};
-    /// use uiohook_rs::HookError;
-    /// # use std::thread::sleep;
-    /// # use std::time::Duration;
+    /// use uiohook_rs::hook::session::HookSession;
+    /// use uiohook_rs::ErrorKind;
     ///
     /// fn on_mouse_click(event: &HookEvent) {
     ///     if let EventKind::MouseMoved(data) = &event.kind {
@@ -524,9 +225,10 @@ impl HookHandle {
     ///     }
     /// }
     ///
+    /// // continue the panics of the hooks on the control thread.
+    /// HookSession::global().on_panic(|panic| std::panic::resume_unwind(panic.payload));
     /// let _id = register_hook(on_mouse_click);
     /// let handle = hook_start().expect("oops hook is already running");
-    /// sleep(Duration::from_millis(10)); // sleep to give everything time to set be set up.
     ///
     /// // The user moves his mouse...
     /// # HookEvent::mouse(MouseButton::NoButton).moved(10, 10).post().expect("couldnt post event");
@@ -534,17 +236,48 @@ impl HookHandle {
     /// // we ignore all other errors and Ok states just to illustrate the resume unwind functionality,
     /// // since we already know the kind of error we get in this contrived example.
     /// if let Err(e) = handle.wait() {
-    ///     match e {
-    ///         HookError::Unknown(str, panic) => std::panic::resume_unwind(panic),
-    ///         _ => (),
+    ///     if e.kind() == ErrorKind::Panic {
+    ///         std::panic::resume_unwind(e.into_panic().unwrap());
     ///     }
     /// }
     /// ```
+    ///
+    /// [`ErrorKind::Panic`]: crate::ErrorKind::Panic
+    /// [`HookSession::on_panic`]: crate::hook::session::HookSession::on_panic
     pub fn wait(self) -> Result<(), HookError> {
-        match self.handle.join() {
-            Ok(hook_thread) => hook_thread.join()?,
-            Err(control_thread_panic) => Err(control_thread_panic.into()),
-        }
+        self.handle.join()?
+    }
+
+    /// Same as [`wait`], only instead of blocking the current thread it returns a future
+    /// that completes once the control thread completes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use futures::executor::block_on;
+    ///
+    /// use uiohook_rs::hook::global::{hook_start, hook_stop};
+    ///
+    /// let handle = hook_start().expect("oops hook is already running");
+    /// hook_stop().unwrap();
+    ///
+    /// block_on(async move { handle.wait_async().await.unwrap() });
+    /// ```
+    ///
+    /// [`wait`]: HookHandle::wait
+    #[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub async fn wait_async(self) -> Result<(), HookError> {
+        // The control thread never sends on this channel, it only drops the sender when it exits,
+        // so receiving completes with a disconnection error once there is nothing left to wait for.
+        let _ = self.done.recv_async().await;
+        self.handle.join()?
+    }
+
+    /// Take a snapshot of the runtime statistics of the session this handle belongs to,
+    /// see [`HookSession::stats`].
+    pub fn stats(&self) -> HookStats {
+        self.session.stats()
     }
 
     /// Stop hook and wait for the control and hook threads to complete.
@@ -555,6 +288,7 @@ impl HookHandle {
     ///
     /// ## Manually Stopping
     /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
     /// use uiohook_rs::hook::event::HookEvent;
     /// use uiohook_rs::hook::global::{hook_start, hook_stop, register_hook};
     /// # use std::thread::sleep;
@@ -575,6 +309,7 @@ impl HookHandle {
     /// ```
     /// ## Using Stop Method
     /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
     /// use uiohook_rs::hook::event::HookEvent;
     /// use uiohook_rs::hook::global::{hook_start, hook_stop, register_hook};
     /// # use std::thread::sleep;
@@ -591,7 +326,7 @@ impl HookHandle {
     ///
     /// [`wait`]: HookHandle::wait
     pub fn stop(self) -> Result<(), HookError> {
-        match hook_stop() {
+        match self.session.stop() {
             Ok(_) => self.wait(),
             Err(err) => Err(err),
         }
@@ -602,29 +337,97 @@ impl HookHandle {
 /// additional threads in order to handle user events and activate callbacks. More in depth explanation
 /// is available at the module level documentation.
 ///
-/// This function will return `Ok(HookHandle)` if this process did not call it already, otherwise
+/// This function will return `Ok(HookHandle)` if the default session is not already running, otherwise
 /// `None` will be returned.
 pub fn hook_start() -> Option<HookHandle> {
-    match RUNNING.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst) {
-        Ok(_) => {
-            let control_thread = thread::spawn(control_thread_main);
-            // After spawning the control thread, we dont want to return immediately because
-            // the user could attempt to post an event before the control and hook threads were properly
-            // initialized. We use the this condvar to wait until the control thread notifies us that
-            // it is initialized.
-            let (ref lock, ref cond) = ENABLED;
-            let mut ready = lock.lock();
-            if !*ready {
-                cond.wait(&mut ready);
-            }
-            // We set ready back to false so that if `hook_stop` is called later and than this function
-            // is called again we wont skip the wait on the condvar.
-            *ready = false;
+    HookSession::global().start()
+}
 
-            Some(HookHandle::from_raw(control_thread))
-        }
-        Err(_) => None,
-    }
+/// Similar to [`hook_start`], only instead of waiting indefinitely for the native hook to be enabled,
+/// this function waits at most `timeout`.
+///
+/// If the native hook is not enabled in time the default session is stopped and an error of kind
+/// [`ErrorKind::TimedOut`] is returned. If the native hook fails before it is enabled its error is returned,
+/// and if the default session is already running an error of kind [`ErrorKind::AlreadyRunning`] is returned.
+///
+/// The timeout includes the time it takes for a native hook that is still stopping to exit. A native hook
+/// that is enabled after the start timed out is stopped right away, unless another session started using it.
+///
+/// The hook can be started and stopped any number of times, each run starts from a clean state.
+///
+/// # Example
+/// ```rust
+/// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::time::Duration;
+///
+/// use uiohook_rs::hook::event::Key;
+/// use uiohook_rs::hook::global::{hook_start_with_timeout, register_hook};
+/// use uiohook_rs::{EventKind, HookEvent};
+///
+/// static PRESSED: AtomicUsize = AtomicUsize::new(0);
+///
+/// register_hook(|event| {
+///     if let EventKind::KeyPressed(_) = event.kind {
+///         PRESSED.fetch_add(1, Ordering::SeqCst);
+///     }
+/// });
+///
+/// // toggle the hook on and off.
+/// for _ in 0..3 {
+///     let handle = hook_start_with_timeout(Duration::from_secs(1)).expect("oops hook did not start");
+///     HookEvent::keyboard(Key::A).press().post().unwrap();
+///     handle.stop().unwrap();
+/// }
+///
+/// assert_eq!(PRESSED.load(Ordering::SeqCst), 3);
+/// ```
+///
+/// ## Timing Out
+/// ```rust
+/// use std::sync::{Condvar, Mutex};
+/// use std::time::Duration;
+///
+/// use uiohook_rs::hook::backend::{Backend, DispatchProc};
+/// use uiohook_rs::hook::global::{hook_start_with_timeout, set_backend};
+/// use uiohook_rs::{ErrorKind, HookError, HookEvent};
+///
+/// /// A backend that never sends the `Enabled` event.
+/// #[derive(Default)]
+/// struct Stuck {
+///     stopped: (Mutex<bool>, Condvar),
+/// }
+///
+/// impl Backend for Stuck {
+///     fn start(&self) -> Result<(), HookError> {
+///         let (ref lock, ref cond) = self.stopped;
+///         let mut stopped = lock.lock().unwrap();
+///         while !*stopped {
+///             stopped = cond.wait(stopped).unwrap();
+///         }
+///         Ok(())
+///     }
+///
+///     fn stop(&self) -> Result<(), HookError> {
+///         *self.stopped.0.lock().unwrap() = true;
+///         self.stopped.1.notify_all();
+///         Ok(())
+///     }
+///
+///     fn post(&self, _event: HookEvent) {}
+///
+///     fn set_dispatch_proc(&self, _dispatch: Option<DispatchProc>) {}
+/// }
+///
+/// set_backend(Stuck::default());
+/// let res = hook_start_with_timeout(Duration::from_millis(50));
+/// assert_eq!(res.err().unwrap().kind(), ErrorKind::TimedOut);
+/// ```
+///
+/// [`ErrorKind::TimedOut`]: crate::ErrorKind::TimedOut
+/// [`ErrorKind::AlreadyRunning`]: crate::ErrorKind::AlreadyRunning
+pub fn hook_start_with_timeout(timeout: Duration) -> Result<HookHandle, HookError> {
+    HookSession::global().start_with_timeout(timeout)
 }
 
 /// Similar to hook start only it is blocking and spawns just one additional thread. See the module
@@ -637,19 +440,74 @@ pub fn hook_start() -> Option<HookHandle> {
 /// registered hooks, or another thread. Otherwise the hook thread will run indefinitely and this function
 /// will never return.
 pub fn hook_start_blocking() -> Result<(), HookError> {
-    match RUNNING.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst) {
-        Ok(_) => control_thread_main().join()?,
-        Err(_) => Ok(()),
-    }
+    HookSession::global().start_blocking()
 }
 
 /// This function attempts to stop the `hook_thread`, returning an error if unsuccessful.
 ///
 /// If the function succeeds in stopping the `hook_thread` the `control_thread` will finish
-/// quickly after. Use [`HookHandle::wait`] to block until the `control_thread` finishes as well.
+/// quickly after. If other sessions are still running the `hook_thread` keeps running for them, and only
+/// the default session is stopped. Use [`HookHandle::wait`] to block until the `control_thread` finishes as well.
 /// It is generally easier to use the [`HookHandle::stop`], see its documentation for further comparison.
 pub fn hook_stop() -> Result<(), HookError> {
-    native::hook_stop()
+    HookSession::global().stop()
+}
+
+/// Take a snapshot of the runtime statistics of the default session, see the [`stats`] module.
+///
+/// [`stats`]: crate::hook::stats
+pub fn hook_stats() -> HookStats {
+    HookSession::global().stats()
+}
+
+/// The live keyboard state of the default session, see [`HookSession::keyboard_state`].
+pub fn keyboard_state() -> Arc<KeyboardState> {
+    HookSession::global().keyboard_state()
+}
+
+/// The live mouse state of the default session, see [`HookSession::mouse_state`].
+pub fn mouse_state() -> Arc<MouseState> {
+    HookSession::global().mouse_state()
+}
+
+/// Replace the [`Backend`] used to run the hook and post events.
+///
+/// The backend is shared by all the sessions, so it can only be replaced while no session is running,
+/// if any session is running this function will return `false` and the backend will not be replaced.
+/// By default the library uses [`NativeBackend`].
+///
+/// # Example
+/// ```rust
+/// use uiohook_rs::hook::backend::NativeBackend;
+/// use uiohook_rs::hook::global::set_backend;
+///
+/// // the hook was not started yet so we can replace the backend.
+/// assert!(set_backend(NativeBackend));
+/// ```
+pub fn set_backend<B: Backend + 'static>(backend: B) -> bool {
+    driver::set_backend(Arc::new(backend))
+}
+
+/// Create a [`Stream`] of all the events received by the default session,
+/// see [`HookSession::events`].
+///
+/// [`Stream`]: futures_core::Stream
+/// [`HookSession::events`]: crate::hook::session::HookSession::events
+#[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+#[cfg(feature = "async")]
+pub fn events() -> EventStream {
+    HookSession::global().events()
+}
+
+/// Create a [`Stream`] of the events of the specified types received by the default session,
+/// see [`HookSession::events_of`].
+///
+/// [`Stream`]: futures_core::Stream
+/// [`HookSession::events_of`]: crate::hook::session::HookSession::events_of
+#[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+#[cfg(feature = "async")]
+pub fn events_of<I: IntoIterator<Item = EventType>>(types: I) -> EventStream {
+    HookSession::global().events_of(types)
 }
 
 /// HookId is a convenience type to represent the id of a hook.
@@ -668,32 +526,41 @@ pub type HookId = u128;
 ///
 /// [`Hook`]: crate::hook::Hook
 pub fn register_hook<F: Fn(&HookEvent) + Sync + Send + 'static>(handler: F) -> HookId {
-    register_boxed_hook(Box::new(handler))
+    HookSession::global().register_hook(handler)
 }
 
-pub(crate) fn register_boxed_hook(handler: HookCallback) -> HookId {
-    static HOOK_ID: Mutex<u128> = const_mutex(0u128);
-
-    let id = {
-        // This is basically the `fetch_add`, only rust doest have
-        // 128 bit  atomic types on stable, so we use a mutex instead.
-        let guard = &mut *HOOK_ID.lock();
-
-        // We use wrapping add to guarantee that there is no overflow panic.
-        // This code might theoretically be erroneous if we manage to overflow the
-        // hook id and the hook id 0 is still in the hashmap, but that would require
-        // calling this function 2^128 times which is practically impossible.
-        let new_id = guard.wrapping_add(1);
-        *guard = new_id;
-        new_id
-    };
-
-    HOOKS.insert(id, handler);
-    id
+/// Similar to [`register_hook`], only the hook will be dispatched according to the specified [`HookOptions`],
+/// which control the priority of the hook and on which thread it is called.
+/// A [`DispatchMode`] can be passed instead of the options, to only change the thread.
+///
+/// See the [`dispatch`] module for more information.
+///
+/// [`HookOptions`]: crate::hook::dispatch::HookOptions
+/// [`DispatchMode`]: crate::hook::dispatch::DispatchMode
+/// [`dispatch`]: crate::hook::dispatch
+pub fn register_hook_with<O, F>(options: O, handler: F) -> HookId
+where
+    O: Into<HookOptions>,
+    F: Fn(&HookEvent) + Sync + Send + 'static,
+{
+    HookSession::global().register_hook_with(options, handler)
 }
 
-pub(crate) fn register_boxed_hook_with_id(id: HookId, handler: HookCallback) {
-    HOOKS.insert(id, handler);
+pub(crate) fn next_hook_id() -> HookId {
+    static HOOK_ID: Mutex<u128> = const_mutex(0u128);
+
+    // This is basically the `fetch_add`, only rust doest have
+    // 128 bit  atomic types on stable, so we use a mutex instead.
+    let guard = &mut *HOOK_ID.lock();
+
+    // We use wrapping add to guarantee that there is no overflow panic.
+    // This code might theoretically be erroneous if we manage to overflow the
+    // hook id and the hook id 0 is still in the hashmap, but that would require
+    // calling this function 2^128 times which is practically impossible.
+    // The ids are shared by all sessions so that a hook id is unique in the process.
+    let new_id = guard.wrapping_add(1);
+    *guard = new_id;
+    new_id
 }
 
 /// Unregister a hook handler, this will remove the handler corresponding to the [`HookId`],
@@ -702,19 +569,19 @@ pub(crate) fn register_boxed_hook_with_id(id: HookId, handler: HookCallback) {
 /// If the provided [`HookId`] does not correspond to a registered hook this function will return
 ///None, otherwise the unregistered hook will be returned.
 pub fn unregister_hook(hook_id: HookId) -> Option<HookCallback> {
-    HOOKS.remove(&hook_id).map(|(_, callback)| callback)
+    HookSession::global().unregister_hook(hook_id)
 }
 
 /// Exactly the same as [`unregister_hook`] except this function does not return anything,
 /// if the [`HookId`] is valid the hook is dropped, otherwise nothing happens.
 pub fn drop_hook(hook_id: HookId) {
-    HOOKS.remove(&hook_id);
+    HookSession::global().drop_hook(hook_id)
 }
 
-pub(crate) fn postable_event(event: &HookEvent) -> Result<(), PostEventError> {
+pub(crate) fn postable_event(event: &HookEvent) -> Result<(), HookError> {
     match &event.kind {
-        EventKind::Enabled => Err(PostEventError("Enabled".into())),
-        EventKind::Disabled => Err(PostEventError("Disabled".into())),
+        EventKind::Enabled => Err(HookError::invalid_event("Enabled")),
+        EventKind::Disabled => Err(HookError::invalid_event("Disabled")),
         _ => Ok(()),
     }
 }
@@ -722,7 +589,7 @@ pub(crate) fn postable_event(event: &HookEvent) -> Result<(), PostEventError> {
 /// Post a [`HookEvent`], this will simulate the user creating the same event through the use of
 /// the mouse and keyboard.
 ///
-/// This function will return a `PostEventError` if the caller attempts to post
+/// This function will return a [`HookError`] of kind [`ErrorKind::InvalidEvent`] if the caller attempts to post
 /// an [`Enabled`] or [`Disabled`] event. These events cant be posted as they are control events
 /// internal to the library, in order to enable and disable the hook use the [`hook_start`] and
 /// [`hook_stop`] API's respectfully.
@@ -731,10 +598,11 @@ pub(crate) fn postable_event(event: &HookEvent) -> Result<(), PostEventError> {
 ///
 /// [`Enabled`]: EventKind::Enabled
 /// [`Disabled`]: EventKind::Disabled
-pub fn post_event(event: HookEvent) -> Result<(), PostEventError> {
+/// [`ErrorKind::InvalidEvent`]: crate::ErrorKind::InvalidEvent
+pub fn post_event(event: HookEvent) -> Result<(), HookError> {
     let res = postable_event(&event);
     if res.is_ok() {
-        native::post_event(event);
+        driver::backend().post(event);
     }
     res
 }
@@ -767,6 +635,7 @@ pub fn post_event(event: HookEvent) -> Result<(), PostEventError> {
 ///
 /// # Example:
 /// ```rust
+/// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
 /// use uiohook_rs::hook::event::EventMode;
 /// use uiohook_rs::hook::global::{register_hook, reserve_events};
 /// use uiohook_rs::hook_start;
@@ -782,10 +651,7 @@ pub fn post_event(event: HookEvent) -> Result<(), PostEventError> {
 #[cfg_attr(rustdoc, doc(cfg(any(target_os = "windows", target_os = "macos"))))]
 #[cfg(any(rustdoc, target_os = "windows", target_os = "macos"))]
 pub unsafe fn reserve_events<F: Fn(&HookEvent) -> bool + Sync + Send + 'static>(filter: F) {
-    std::mem::swap(
-        RESERVE_CALLBACK.lock().deref_mut(),
-        &mut Some(Box::new(filter)),
-    )
+    HookSession::global().reserve_events(filter)
 }
 
 // we define an empty reserve_events function when in test mode to allow the tests
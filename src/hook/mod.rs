@@ -6,16 +6,33 @@ use std::mem;
 use once_cell::sync::Lazy;
 use strum::IntoEnumIterator;
 
+use crate::hook::dispatch::{DispatchMode, HookOptions};
 use crate::hook::event::{
     EventKind, EventMetaData, HookEvent, Key, KeyboardEvent, MouseButton, MouseEvent,
     MouseWheelEvent,
 };
 use crate::hook::global::HookId;
+use crate::hook::session::HookSession;
 
+pub(crate) mod clock;
 pub(crate) mod constants;
+mod driver;
 
+pub mod backend;
+pub mod bus;
+pub mod dispatch;
 pub mod event;
 pub mod global;
+#[cfg_attr(rustdoc, doc(cfg(feature = "serde")))]
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod session;
+pub mod state;
+pub mod stats;
+#[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod supervisor;
 
 static KEY_SET: Lazy<HashSet<Key, ahash::RandomState>> = Lazy::new(|| Key::iter().collect());
 static MOUSE_BUTTON_SET: Lazy<HashSet<MouseButton, ahash::RandomState>> =
@@ -507,6 +524,8 @@ pub enum HookOn<I: IntoIterator> {
 pub struct Hook {
     hook: Option<Box<dyn Fn(&HookEvent) + Sync + Send + 'static>>,
     id: Option<HookId>,
+    session: Option<HookSession>,
+    options: HookOptions,
 }
 
 impl Hook {
@@ -525,6 +544,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(callback)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -539,7 +560,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, Key, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Key, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -581,6 +602,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -596,7 +619,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, Key, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Key, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -653,6 +676,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -667,7 +692,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -709,6 +734,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -724,7 +751,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -781,6 +808,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -797,7 +826,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -834,6 +863,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -850,7 +881,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -865,7 +896,7 @@ impl Hook {
     ///
     /// // we create a mouse wheel event, and post it.
     /// HookEvent::mouse(MouseButton::Left)
-    ///     .with_mask(EventMask::LeftMouseButton)
+    ///     .with_mask(Modifiers::LEFT_MOUSE_BUTTON)
     ///     .dragged(10, 10)
     ///     .post();
     ///
@@ -890,6 +921,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -915,7 +948,7 @@ impl Hook {
     /// # use std::thread::sleep;
     /// # use std::time::Duration;
     /// use uiohook_rs::hook::event::{
-    ///     EventIterator, EventMask, MouseButton, MouseScrollDirection, MouseScrollKind,
+    ///     EventIterator, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
     /// };
     /// use uiohook_rs::hook::HookOn;
     /// use uiohook_rs::{hook_start, Hook, HookEvent};
@@ -940,7 +973,7 @@ impl Hook {
     ///
     /// // we create a mouse wheel event, and post it.
     /// HookEvent::mouse(MouseButton::Left)
-    ///     .with_mask(EventMask::LeftMouseButton)
+    ///     .with_mask(Modifiers::LEFT_MOUSE_BUTTON)
     ///     .dragged(10, 10)
     ///     .post();
     ///
@@ -979,6 +1012,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -1035,6 +1070,8 @@ impl Hook {
         Hook {
             hook: Some(Box::new(hook)),
             id: None,
+            session: None,
+            options: HookOptions::default(),
         }
     }
 
@@ -1058,13 +1095,118 @@ impl Hook {
     /// ```
     pub fn register(&mut self) {
         if let Some(callback) = mem::replace(&mut self.hook, None) {
+            let session = self.session();
             match self.id {
-                Some(id) => global::register_boxed_hook_with_id(id, callback),
-                None => self.id = Some(global::register_boxed_hook(callback)),
+                Some(id) => session.register_boxed_hook_with_id(id, callback, self.options),
+                None => self.id = Some(session.register_boxed_hook(callback, self.options)),
             }
         }
     }
 
+    /// Set the [`DispatchMode`] of the hook, which decides on which thread the hook is called.
+    ///
+    /// If the hook is already registered, the new mode takes effect the next time the hook is registered.
+    ///
+    /// # Example
+    ///```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use uiohook_rs::hook::dispatch::DispatchMode;
+    /// use uiohook_rs::{hook_start, Hook};
+    ///
+    /// // the hook will be called on its own thread, so it can't delay other hooks.
+    /// let mut slow = Hook::new(|event| println!("{:?}", event)).with_dispatch(DispatchMode::Dedicated);
+    /// slow.register();
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// handle.stop().unwrap();
+    /// ```
+    ///
+    /// [`DispatchMode`]: crate::hook::dispatch::DispatchMode
+    pub fn with_dispatch(mut self, mode: DispatchMode) -> Self {
+        self.options = self.options.with_dispatch(mode);
+        self
+    }
+
+    /// Set the priority of the hook, hooks with a higher priority receive the events first,
+    /// and can prevent hooks with a lower priority from receiving them, see [`consume_event`].
+    ///
+    /// If the hook is already registered, the new priority takes effect the next time the hook is registered.
+    ///
+    /// # Example
+    ///```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use uiohook_rs::hook::dispatch::consume_event;
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::hook::HookOn;
+    /// use uiohook_rs::{hook_start, Hook, HookEvent};
+    ///
+    /// let app_calls = Arc::new(AtomicUsize::new(0));
+    /// let overlay_calls = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let counter = app_calls.clone();
+    /// let mut app = Hook::keys(HookOn::OneOf([Key::Escape]), move |_, _| {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    /// });
+    /// app.register();
+    ///
+    /// let counter = overlay_calls.clone();
+    /// let mut overlay = Hook::keys(HookOn::OneOf([Key::Escape]), move |_, _| {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    ///     consume_event();
+    /// })
+    /// .with_priority(1);
+    /// overlay.register();
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// HookEvent::keyboard(Key::Escape).pair().post().unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// // the overlay consumed the escape key, so the app never received it.
+    /// assert!(overlay_calls.load(Ordering::SeqCst) > 0);
+    /// assert_eq!(app_calls.load(Ordering::SeqCst), 0);
+    /// ```
+    ///
+    /// [`consume_event`]: crate::hook::dispatch::consume_event
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.options = self.options.with_priority(priority);
+        self
+    }
+
+    /// Register the hook with a specific [`HookSession`] instead of the default one.
+    ///
+    /// If the hook is currently registered with another session, it is moved to the new session.
+    ///
+    /// # Example
+    ///```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use uiohook_rs::hook::session::HookSession;
+    /// use uiohook_rs::Hook;
+    ///
+    /// let session = HookSession::new();
+    /// let mut log_all = Hook::new(|event| println!("{:?}", event));
+    /// log_all.register_in(&session);
+    ///
+    /// // only the hooks registered with the session are called.
+    /// let handle = session.start().expect("oops session already running");
+    /// handle.stop().unwrap();
+    /// ```
+    ///
+    /// [`HookSession`]: crate::hook::session::HookSession
+    pub fn register_in(&mut self, session: &HookSession) {
+        self.unregister();
+        self.session = Some(session.clone());
+        self.register();
+    }
+
+    fn session(&self) -> &HookSession {
+        self.session
+            .as_ref()
+            .unwrap_or_else(|| HookSession::global())
+    }
+
     /// Unregister the hook, making it stop listening for events.
     ///
     /// # Example
@@ -1090,7 +1232,7 @@ impl Hook {
     /// ```
     pub fn unregister(&mut self) {
         if let Some(id) = self.id {
-            if let Some(callback) = global::unregister_hook(id) {
+            if let Some(callback) = self.session().unregister_hook(id) {
                 self.hook = Some(callback);
             }
         }
@@ -1100,7 +1242,7 @@ impl Hook {
 impl Drop for Hook {
     fn drop(&mut self) {
         if let Some(id) = self.id {
-            global::drop_hook(id);
+            self.session().drop_hook(id);
         }
     }
 }
@@ -0,0 +1,278 @@
+//! Serialization of the events with [`serde`].
+//!
+//! This module is only available with the `serde` feature, which implements [`Serialize`] and
+//! [`Deserialize`] for the events and the types they are made of, see [`HookEvent`],
+//! as well as for [`ScreenData`].
+//!
+//! The encoding depends on whether the format is human readable, see [`Serializer::is_human_readable`]:
+//!
+//! *   In human readable formats such as JSON, keys, mouse buttons and the other enums are encoded with
+//!     the name of their variant, like `"LeftControl"`, and the [`Modifiers`] and the [`EventMode`] are
+//!     encoded as a list of the names of their flags, like `["LEFT_CONTROL", "CAPS_LOCK"]`. Values
+//!     unknown to this library, such as [`Key::Unknown`], are encoded with their native code instead.
+//!     The side agnostic flags such as [`Modifiers::SHIFT`] are encoded as both sides, but are
+//!     accepted when decoding.
+//! *   In compact formats such as bincode, the enums and flags are encoded with their native code.
+//!
+//! Both encodings are stable, names and codes are only ever added.
+//!
+//! # Example
+//! ```rust
+//! use uiohook_rs::hook::event::{Key, Modifiers};
+//! use uiohook_rs::{EventKind, HookEvent};
+//!
+//! let event = HookEvent::keyboard(Key::C)
+//!     .with_mask(Modifiers::LEFT_CONTROL)
+//!     .press();
+//! let json = serde_json::to_value(&event).unwrap();
+//! assert_eq!(json["metadata"]["mask"], serde_json::json!(["LEFT_CONTROL"]));
+//! assert_eq!(json["kind"]["KeyPressed"]["keycode"], "C");
+//!
+//! let decoded: HookEvent = serde_json::from_value(json).unwrap();
+//! assert!(matches!(decoded.kind, EventKind::KeyPressed(data) if data.keycode == Key::C));
+//! assert_eq!(decoded.metadata.mask, Modifiers::LEFT_CONTROL);
+//!
+//! // the side agnostic flags are accepted, and are encoded as both sides.
+//! let shift: Modifiers = serde_json::from_str(r#"["SHIFT"]"#).unwrap();
+//! assert_eq!(shift, Modifiers::SHIFT);
+//! let json = serde_json::to_string(&shift).unwrap();
+//! assert_eq!(json, r#"["LEFT_SHIFT","RIGHT_SHIFT"]"#);
+//! ```
+//!
+//! [`HookEvent`]: crate::hook::event::HookEvent
+//! [`ScreenData`]: crate::system_properties::ScreenData
+//! [`Serializer::is_human_readable`]: serde::Serializer::is_human_readable
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use crate::hook::event::{
+    EventMode, Key, Modifiers, MouseButton, MouseScrollDirection, MouseScrollKind,
+};
+
+/// Implement the name or code encoding of an enum made by `constant_to_enum`.
+macro_rules! serialize_enum {
+    ($($enum_name:ident => $native:ty),+ $(,)*) => {
+        $(
+            impl Serialize for $enum_name {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    match self {
+                        $enum_name::Unknown(_) => <$native>::from(*self).serialize(serializer),
+                        _ if serializer.is_human_readable() => {
+                            serializer.collect_str(self)
+                        }
+                        _ => <$native>::from(*self).serialize(serializer),
+                    }
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $enum_name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    if deserializer.is_human_readable() {
+                        deserializer.deserialize_any(NameOrCode::<$enum_name, $native>::new())
+                    } else {
+                        <$native>::deserialize(deserializer).map($enum_name::from)
+                    }
+                }
+            }
+
+            impl FromName for $enum_name {
+                fn from_name(name: &str) -> Option<Self> {
+                    // strum parses the name of the unknown variant as well.
+                    match name.parse() {
+                        Ok($enum_name::Unknown(_)) | Err(_) => None,
+                        Ok(value) => Some(value),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+/// Implement the list of names or bits encoding of a set of flags.
+macro_rules! serialize_flags {
+    ($($flags:ident => [$($flag:ident),+ $(,)*]),+ $(,)*) => {
+        $(
+            impl $flags {
+                const NAMES: &'static [(&'static str, $flags)] = &[
+                    $((stringify!($flag), $flags::$flag)),+
+                ];
+            }
+
+            impl Serialize for $flags {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    if !serializer.is_human_readable() {
+                        return self.bits().serialize(serializer);
+                    }
+
+                    // Only single flags are listed, the side agnostic flags are made of both sides.
+                    let names: Vec<&str> = $flags::NAMES
+                        .iter()
+                        .filter(|(_, flag)| flag.bits().count_ones() == 1 && self.contains(*flag))
+                        .map(|(name, _)| *name)
+                        .collect();
+                    let mut seq = serializer.serialize_seq(Some(names.len()))?;
+                    for name in names {
+                        seq.serialize_element(name)?;
+                    }
+                    seq.end()
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $flags {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    if deserializer.is_human_readable() {
+                        deserializer.deserialize_any(FlagsVisitor::<$flags>::new())
+                    } else {
+                        u16::deserialize(deserializer).map($flags::from_bits_truncate)
+                    }
+                }
+            }
+
+            impl Flags for $flags {
+                const EXPECTING: &'static str = concat!("a list of ", stringify!($flags), " flags");
+
+                fn from_name(name: &str) -> Option<Self> {
+                    $flags::NAMES
+                        .iter()
+                        .find(|(flag_name, _)| *flag_name == name)
+                        .map(|(_, flag)| *flag)
+                }
+
+                fn from_bits(bits: u16) -> Self {
+                    $flags::from_bits_truncate(bits)
+                }
+
+                fn union(self, other: Self) -> Self {
+                    self | other
+                }
+            }
+        )+
+    };
+}
+
+serialize_enum! {
+    Key => u16,
+    MouseButton => u16,
+    MouseScrollKind => u8,
+    MouseScrollDirection => u8,
+}
+
+serialize_flags! {
+    Modifiers => [
+        LEFT_SHIFT,
+        LEFT_CONTROL,
+        LEFT_META,
+        LEFT_ALT,
+        RIGHT_SHIFT,
+        RIGHT_CONTROL,
+        RIGHT_META,
+        RIGHT_ALT,
+        SHIFT,
+        CONTROL,
+        META,
+        ALT,
+        LEFT_MOUSE_BUTTON,
+        RIGHT_MOUSE_BUTTON,
+        MIDDLE_MOUSE_BUTTON,
+        EXTRA_MOUSE_BUTTON1,
+        EXTRA_MOUSE_BUTTON2,
+        NUM_LOCK,
+        CAPS_LOCK,
+        SCROLL_LOCK,
+    ],
+    EventMode => [RESERVED, SYNTHETIC, REPEAT],
+}
+
+trait FromName: Sized {
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+/// Decodes an enum from the name of its variant or from its native code.
+struct NameOrCode<T, N> {
+    marker: std::marker::PhantomData<(T, N)>,
+}
+
+impl<T, N> NameOrCode<T, N> {
+    fn new() -> Self {
+        NameOrCode {
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, T, N> Visitor<'de> for NameOrCode<T, N>
+where
+    T: FromName + From<N>,
+    N: TryFrom<u64> + TryFrom<i64>,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a name or a native code")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<T, E> {
+        T::from_name(name).ok_or_else(|| E::invalid_value(de::Unexpected::Str(name), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, code: u64) -> Result<T, E> {
+        N::try_from(code)
+            .map(T::from)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(code), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, code: i64) -> Result<T, E> {
+        N::try_from(code)
+            .map(T::from)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(code), &self))
+    }
+}
+
+trait Flags: Sized {
+    const EXPECTING: &'static str;
+
+    fn from_name(name: &str) -> Option<Self>;
+    fn from_bits(bits: u16) -> Self;
+    fn union(self, other: Self) -> Self;
+}
+
+/// Decodes flags from a list of names, or from their bits.
+struct FlagsVisitor<T> {
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T> FlagsVisitor<T> {
+    fn new() -> Self {
+        FlagsVisitor {
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Flags> Visitor<'de> for FlagsVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(T::EXPECTING)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let mut flags = T::from_bits(0);
+        while let Some(name) = seq.next_element::<String>()? {
+            let flag = T::from_name(&name)
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(&name), &self))?;
+            flags = flags.union(flag);
+        }
+        Ok(flags)
+    }
+
+    fn visit_u64<E: de::Error>(self, bits: u64) -> Result<T, E> {
+        u16::try_from(bits)
+            .map(T::from_bits)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(bits), &self))
+    }
+}
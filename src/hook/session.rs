@@ -0,0 +1,926 @@
+//! Independent hook sessions.
+//!
+//! A [`HookSession`] owns everything needed to run hooks, its own hook registry, event bus,
+//! reserve filter and control thread. This means that several libraries in the same process can each
+//! create their own session without interfering with each other's hooks, for example, stopping one session
+//! does not stop the hooks registered with another.
+//!
+//! There is only one native hook per process, so all the running sessions share it. The native hook is
+//! started when the first session starts, and stopped when the last running session is stopped,
+//! every event generated by the native hook is distributed to all the running sessions.
+//!
+//! The free functions in the [`global`] module operate on the default session, which is available
+//! through [`HookSession::global`].
+//!
+//! [`global`]: crate::hook::global
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::session::HookSession;
+//! use uiohook_rs::HookEvent;
+//!
+//! static FIRST: AtomicUsize = AtomicUsize::new(0);
+//! static SECOND: AtomicUsize = AtomicUsize::new(0);
+//!
+//! let first = HookSession::new();
+//! let second = HookSession::new();
+//! first.register_hook(|_| {
+//!     FIRST.fetch_add(1, Ordering::SeqCst);
+//! });
+//! second.register_hook(|_| {
+//!     SECOND.fetch_add(1, Ordering::SeqCst);
+//! });
+//!
+//! let first_handle = first.start().expect("oops session already running");
+//! let second_handle = second.start().expect("oops session already running");
+//!
+//! // both sessions receive the event.
+//! HookEvent::keyboard(Key::A).press().post().unwrap();
+//!
+//! // stopping the first session does not affect the second.
+//! first_handle.stop().unwrap();
+//! HookEvent::keyboard(Key::A).release().post().unwrap();
+//! second_handle.stop().unwrap();
+//!
+//! // the first session saw Enabled, KeyPressed and Disabled
+//! assert_eq!(FIRST.load(Ordering::SeqCst), 3);
+//! // the second session also saw the KeyReleased event.
+//! assert_eq!(SECOND.load(Ordering::SeqCst), 4);
+//! ```
+
+use std::cmp::Reverse;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::{const_mutex, const_rwlock, Condvar, Mutex, RwLock};
+
+use crate::error::{ErrorKind, HookError, HookPanic, Operation};
+use crate::hook::bus::{EventBus, OverflowPolicy};
+use crate::hook::dispatch::{
+    self, DispatchMode, HookOptions, PanicPolicy, Pool, Worker, WorkerQueue, DEFAULT_POOL_SIZE,
+};
+use crate::hook::driver;
+#[cfg(feature = "async")]
+use crate::hook::event::EventType;
+use crate::hook::event::{EventKind, HookEvent};
+use crate::hook::global::{next_hook_id, HookHandle, HookId};
+use crate::hook::state::{EventContext, KeyboardState, Lifecycle, MouseState};
+use crate::hook::stats::{CallbackCounter, HookStats, SessionStats};
+#[cfg(feature = "async")]
+use crate::hook::stream::{EventStream, Subscriber};
+use crate::hook::supervisor::LifecycleEvent;
+
+pub(crate) type HookCallback = Box<dyn Fn(&HookEvent) + Sync + Send>;
+pub(crate) type HookFilter = Box<dyn Fn(&HookEvent) -> bool + Sync + Send>;
+type PanicHandler = Box<dyn Fn(HookPanic) + Sync + Send>;
+
+struct RegisteredHook {
+    callback: HookCallback,
+    options: HookOptions,
+    disabled: AtomicBool,
+    stats: CallbackCounter,
+}
+
+impl RegisteredHook {
+    fn call(
+        &self,
+        id: HookId,
+        event: &HookEvent,
+        context: &EventContext,
+        policy: PanicPolicy,
+        stats: &SessionStats,
+    ) -> Option<HookPanic> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        stats.record_latency(event);
+        let start = Instant::now();
+        // We catch the panic here so that a panicking hook does not unwind the thread calling it,
+        // which would stop every other hook called from this thread.
+        let result =
+            context.enter(|| panic::catch_unwind(AssertUnwindSafe(|| (self.callback)(event))));
+        self.stats.record(start.elapsed());
+
+        match result {
+            Ok(_) => None,
+            Err(payload) => {
+                if policy != PanicPolicy::Keep {
+                    self.disabled.store(true, Ordering::Relaxed);
+                }
+                Some(HookPanic::new(id, event.clone(), payload))
+            }
+        }
+    }
+}
+
+static GLOBAL_SESSION: Lazy<HookSession> = Lazy::new(HookSession::new);
+
+/// The ids of the hooks sorted by descending priority, then by id.
+type DispatchOrder = Arc<Vec<(Reverse<i32>, HookId)>>;
+
+pub(crate) struct SessionInner {
+    running: AtomicBool,
+    // Set when the session is stopped on purpose, which tells a supervisor not to restart it.
+    stop_requested: AtomicBool,
+    // The number of times the session was restarted by a supervisor since it was started.
+    restarts: AtomicU32,
+    enabled: (Mutex<bool>, Condvar),
+    bus: EventBus,
+    hooks: DashMap<HookId, RegisteredHook, ahash::RandomState>,
+    // The ids of the hooks in the order they are dispatched, we replace the whole list when it changes
+    // so that the control thread can dispatch an event without holding the lock.
+    order: RwLock<DispatchOrder>,
+    workers: DashMap<HookId, Worker, ahash::RandomState>,
+    pool: Mutex<Option<Pool>>,
+    pool_size: AtomicUsize,
+    reserve: Mutex<Option<HookFilter>>,
+    panic_policy: Mutex<PanicPolicy>,
+    panic_handler: RwLock<Option<PanicHandler>>,
+    exit_status: Mutex<Option<Result<(), HookError>>>,
+    stats: SessionStats,
+    // The state of the input devices, as of the last event received by the control thread.
+    tracked: Mutex<EventContext>,
+    #[cfg(feature = "async")]
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl SessionInner {
+    pub(crate) fn send(&self, event: HookEvent) {
+        self.bus.send(event);
+    }
+
+    pub(crate) fn reserves(&self, event: &HookEvent) -> bool {
+        match &*self.reserve.lock() {
+            Some(filter) => filter(event),
+            None => false,
+        }
+    }
+
+    pub(crate) fn exit(&self, status: Result<(), HookError>) {
+        *self.exit_status.lock() = Some(status);
+    }
+
+    pub(crate) fn call_hook(&self, id: HookId, event: &HookEvent, context: &EventContext) {
+        let policy = *self.panic_policy.lock();
+        let panic = match self.hooks.get(&id) {
+            Some(hook) => hook.call(id, event, context, policy, &self.stats),
+            None => None,
+        };
+
+        if let Some(panic) = panic {
+            self.report_panic(panic, policy);
+        }
+    }
+
+    fn insert_hook(&self, id: HookId, hook: RegisteredHook) {
+        let priority = hook.options.priority;
+        let mut order = self.order.write();
+        self.hooks.insert(id, hook);
+
+        let mut new_order: Vec<_> = order
+            .iter()
+            .filter(|(_, other)| *other != id)
+            .copied()
+            .collect();
+        let entry = (Reverse(priority), id);
+        // hooks with the same priority are ordered by their id, which is the order they were created.
+        let index = new_order
+            .binary_search(&entry)
+            .unwrap_or_else(|index| index);
+        new_order.insert(index, entry);
+        *order = Arc::new(new_order);
+    }
+
+    fn remove_hook(&self, id: HookId) -> Option<RegisteredHook> {
+        // Dropping the worker lets its thread exit once it handled the events already sent to it.
+        self.workers.remove(&id);
+
+        let mut order = self.order.write();
+        let removed = self.hooks.remove(&id).map(|(_, hook)| hook);
+        if removed.is_some() {
+            *order = Arc::new(
+                order
+                    .iter()
+                    .filter(|(_, other)| *other != id)
+                    .copied()
+                    .collect(),
+            );
+        }
+        removed
+    }
+
+    // This must be called after releasing any reference into `hooks` and `workers`,
+    // since the hook might be removed, and the panic handler might register or unregister hooks.
+    fn report_panic(&self, panic: HookPanic, policy: PanicPolicy) {
+        if let PanicPolicy::Remove = policy {
+            self.remove_hook(panic.hook_id);
+        }
+
+        if let Some(handler) = &*self.panic_handler.read() {
+            handler(panic);
+        }
+    }
+
+    fn dispatch(self: &Arc<Self>, event: &HookEvent, context: &EventContext) {
+        let policy = *self.panic_policy.lock();
+        let mut panics = Vec::new();
+        let consumable = !matches!(event.kind, EventKind::Enabled | EventKind::Disabled);
+
+        let order = self.order.read().clone();
+        for (_, id) in order.iter() {
+            let hook = match self.hooks.get(id) {
+                Some(hook) => hook,
+                // the hook was unregistered after we took the order.
+                None => continue,
+            };
+
+            let queue = match hook.options.mode {
+                DispatchMode::Inline => {
+                    dispatch::take_consumed();
+                    if let Some(panic) = hook.call(*id, event, context, policy, &self.stats) {
+                        panics.push(panic);
+                    }
+                    if dispatch::take_consumed() && consumable {
+                        break;
+                    }
+                    continue;
+                }
+                DispatchMode::Dedicated => self.workers.get(id).map(|worker| worker.queue()),
+                DispatchMode::Pool => {
+                    // The pool is created lazily, so that sessions without pooled hooks
+                    // do not spawn any threads.
+                    let mut pool = self.pool.lock();
+                    let pool = pool.get_or_insert_with(|| {
+                        let size = self.pool_size.load(Ordering::Relaxed);
+                        Pool::new(size, Arc::downgrade(self), &self.bus)
+                    });
+                    Some(pool.queue(*id))
+                }
+            };
+            // Sending blocks while the queue of the worker is full with the blocking policies,
+            // so we must not hold on to the hook, which the worker looks up to call it.
+            drop(hook);
+            if let Some(queue) = queue {
+                queue.send(*id, event.clone(), context.clone());
+            }
+        }
+
+        for panic in panics {
+            self.report_panic(panic, policy);
+        }
+    }
+
+    /// The queues of the dedicated workers and of the workers of the pool.
+    ///
+    /// The queues are collected so that no lock on `workers` or `pool` is held while using them,
+    /// a worker might remove hooks while it handles an event, which needs both.
+    fn worker_queues(&self) -> Vec<WorkerQueue> {
+        let mut queues: Vec<_> = self.workers.iter().map(|worker| worker.queue()).collect();
+        if let Some(pool) = &*self.pool.lock() {
+            queues.extend(pool.queues());
+        }
+        queues
+    }
+
+    /// Wait until all the workers handled the events dispatched to them.
+    fn flush_workers(&self) {
+        for queue in self.worker_queues() {
+            queue.flush();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn publish(&self, event: &HookEvent) {
+        let mut subscribers = self.subscribers.lock();
+        // Streams that were dropped are removed lazily, when the next event is published.
+        subscribers.retain(|subscriber| subscriber.publish(event));
+
+        // The streams end when the session is stopped, we drop the senders so that
+        // the streams will complete after yielding the events already sent to them.
+        if let EventKind::Disabled = event.kind {
+            subscribers.clear();
+        }
+    }
+
+    /// The lifecycle event that goes along with a control event, see [`LifecycleEvent::current`].
+    fn lifecycle(&self, event: &HookEvent) -> Option<LifecycleEvent> {
+        match event.kind {
+            EventKind::Enabled => Some(LifecycleEvent::Started {
+                restarts: self.restarts.load(Ordering::SeqCst),
+            }),
+            EventKind::Disabled if self.stop_requested.load(Ordering::SeqCst) => {
+                Some(LifecycleEvent::Stopped)
+            }
+            // The exit status is recorded before the `Disabled` event is sent, see the `driver` module.
+            EventKind::Disabled => Some(LifecycleEvent::Failed {
+                error: match &*self.exit_status.lock() {
+                    Some(Err(err)) => Some(err.duplicate()),
+                    _ => None,
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    fn notify_enabled(&self) {
+        let (ref lock, ref cond) = self.enabled;
+        let mut ready = lock.lock();
+        *ready = true;
+        cond.notify_all();
+    }
+}
+
+/// An independent set of hooks with its own start/stop lifetime.
+///
+/// The session is cheap to clone, and all the clones refer to the same session.
+/// See the [module level documentation](crate::hook::session) for more information.
+#[derive(Clone)]
+pub struct HookSession {
+    inner: Arc<SessionInner>,
+}
+
+impl Default for HookSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HookSession {
+    /// Create a new session with no registered hooks.
+    pub fn new() -> Self {
+        Self::with_bus(None, OverflowPolicy::default())
+    }
+
+    /// Create a new session whose event bus holds at most `capacity` events,
+    /// see the [`bus`] module for more information.
+    ///
+    /// A capacity of `0` is treated as `1`, a bus always has room for at least one event.
+    ///
+    /// [`bus`]: crate::hook::bus
+    pub fn with_capacity(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self::with_bus(Some(capacity), policy)
+    }
+
+    fn with_bus(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        HookSession {
+            inner: Arc::new(SessionInner {
+                running: AtomicBool::new(false),
+                stop_requested: AtomicBool::new(false),
+                restarts: AtomicU32::new(0),
+                enabled: (const_mutex(false), Condvar::new()),
+                bus: EventBus::new(capacity, policy),
+                hooks: DashMap::with_hasher(ahash::RandomState::new()),
+                order: RwLock::new(Arc::new(Vec::new())),
+                workers: DashMap::with_hasher(ahash::RandomState::new()),
+                pool: const_mutex(None),
+                pool_size: AtomicUsize::new(DEFAULT_POOL_SIZE),
+                reserve: const_mutex(None),
+                panic_policy: const_mutex(PanicPolicy::default()),
+                panic_handler: const_rwlock(None),
+                exit_status: const_mutex(None),
+                stats: SessionStats::default(),
+                tracked: const_mutex(EventContext::default()),
+                #[cfg(feature = "async")]
+                subscribers: const_mutex(Vec::new()),
+            }),
+        }
+    }
+
+    /// The default session, used by the free functions in the [`global`] module and by [`Hook`].
+    ///
+    /// [`global`]: crate::hook::global
+    /// [`Hook`]: crate::hook::Hook
+    pub fn global() -> &'static HookSession {
+        &GLOBAL_SESSION
+    }
+
+    /// Change the capacity and overflow policy of the event bus, a capacity of `None` makes the bus unbounded.
+    ///
+    /// The bus can be reconfigured while the session is running, if the new capacity is smaller than the
+    /// number of events in the bus, those events are kept and the overflow policy applies to new events.
+    /// A capacity of `Some(0)` is treated as `Some(1)`.
+    ///
+    /// The queues of the worker threads of the session are reconfigured as well, see the [`dispatch`] module.
+    ///
+    /// [`dispatch`]: crate::hook::dispatch
+    pub fn set_capacity(&self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.inner.bus.configure(capacity, policy);
+        for queue in self.inner.worker_queues() {
+            queue.configure(capacity, policy);
+        }
+    }
+
+    /// The number of events lost since the session was created because the event bus,
+    /// or the queue of a worker thread, was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.inner.bus.dropped()
+    }
+
+    /// Take a snapshot of the runtime statistics of this session, see the [`stats`] module.
+    ///
+    /// [`stats`]: crate::hook::stats
+    pub fn stats(&self) -> HookStats {
+        let hooks = self
+            .inner
+            .hooks
+            .iter()
+            .map(|hook| (*hook.key(), hook.stats.snapshot()))
+            .collect();
+        self.inner
+            .stats
+            .snapshot(self.inner.bus.len(), self.inner.bus.dropped(), hooks)
+    }
+
+    /// The live keyboard state of this session, see the [`state`] module.
+    ///
+    /// Hooks should use [`KeyboardState::current`] instead, which returns the state as of the event
+    /// they are handling.
+    ///
+    /// [`state`]: crate::hook::state
+    pub fn keyboard_state(&self) -> Arc<KeyboardState> {
+        self.inner.tracked.lock().keyboard.clone()
+    }
+
+    /// The live mouse state of this session, see the [`state`] module.
+    ///
+    /// Hooks should use [`MouseState::current`] instead, which returns the state as of the event
+    /// they are handling.
+    ///
+    /// [`state`]: crate::hook::state
+    pub fn mouse_state(&self) -> Arc<MouseState> {
+        self.inner.tracked.lock().mouse.clone()
+    }
+
+    /// Check if the session was started and not yet stopped.
+    pub fn is_running(&self) -> bool {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    fn listen(inner: &Arc<SessionInner>) {
+        loop {
+            let mut event = inner.bus.recv();
+            inner.stats.record_event(&event);
+            let mut context = inner.tracked.lock().track(&mut event);
+            context.lifecycle = inner.lifecycle(&event).map(Lifecycle);
+            if let EventKind::Enabled = &event.kind {
+                // When we receive the enabled event we notify the conditional variable so
+                // that the start function can complete.
+                inner.notify_enabled();
+            }
+
+            inner.dispatch(&event, &context);
+
+            #[cfg(feature = "async")]
+            inner.publish(&event);
+
+            // If the event we received was of the session being disabled
+            // we can stop listening to the hook events.
+            // After breaking out of the listening loop the control thread will
+            // complete.
+            if let EventKind::Disabled = event.kind {
+                break;
+            }
+        }
+    }
+
+    /// Prepare the session for a new run and attach it to the driver.
+    ///
+    /// Returns `false`, leaving the session stopped, if the previous native hook did not exit
+    /// before the deadline.
+    fn attach(inner: &Arc<SessionInner>, deadline: Option<Instant>, restarts: u32) -> bool {
+        inner.exit_status.lock().take();
+        inner.stop_requested.store(false, Ordering::SeqCst);
+        inner.restarts.store(restarts, Ordering::SeqCst);
+        // The control thread of the previous run notifies the condvar when it exits, we reset the
+        // flag so that we only observe the notification of this run.
+        *inner.enabled.0.lock() = false;
+        inner.bus.open();
+        if driver::attach(inner, deadline) {
+            return true;
+        }
+
+        inner.bus.close();
+        inner.running.store(false, Ordering::SeqCst);
+        false
+    }
+
+    fn control_thread_main(inner: Arc<SessionInner>) -> Result<(), HookError> {
+        // The hooks are isolated from each other, but the panic handler is not, if it panics
+        // we detach the session so that it will not receive any more events and clean up as usual.
+        let listened = panic::catch_unwind(AssertUnwindSafe(|| Self::listen(&inner)));
+        if listened.is_err() {
+            let _ = driver::detach(&inner);
+        }
+
+        // The hooks running on worker threads might still be handling events,
+        // the session is only considered stopped once all the hooks have seen the `Disabled` event.
+        inner.flush_workers();
+
+        inner.bus.close();
+        inner.running.store(false, Ordering::SeqCst);
+        // If the native hook failed before it was enabled, the start function is still waiting,
+        // we release it so the error can be observed through the handle.
+        inner.notify_enabled();
+        let exit_status = inner.exit_status.lock().take().unwrap_or(Ok(()));
+        match listened {
+            Ok(_) => exit_status,
+            Err(panic) => Err(panic.into()),
+        }
+    }
+
+    /// Starts listening for user events in a non blocking fashion, see [`hook_start`].
+    ///
+    /// This function will return `Some(HookHandle)` if the session is not already running,
+    /// otherwise `None` will be returned.
+    ///
+    /// [`hook_start`]: crate::hook::global::hook_start
+    pub fn start(&self) -> Option<HookHandle> {
+        // Without a timeout the only possible error is that the session is already running.
+        self.spawn(None, 0).ok()
+    }
+
+    /// Similar to [`start`], only if the native hook is not enabled within `timeout` the session is
+    /// stopped and an error of kind [`ErrorKind::TimedOut`] is returned, see [`hook_start_with_timeout`].
+    ///
+    /// If the session is already running an error of kind [`ErrorKind::AlreadyRunning`] is returned, and if
+    /// the native hook fails before it is enabled, the error of the native hook is returned.
+    ///
+    /// [`ErrorKind::TimedOut`]: crate::ErrorKind::TimedOut
+    /// [`ErrorKind::AlreadyRunning`]: crate::ErrorKind::AlreadyRunning
+    ///
+    /// [`start`]: HookSession::start
+    /// [`hook_start_with_timeout`]: crate::hook::global::hook_start_with_timeout
+    pub fn start_with_timeout(&self, timeout: Duration) -> Result<HookHandle, HookError> {
+        self.spawn(Some(timeout), 0)
+    }
+
+    /// Start the session again on behalf of a supervisor, `restarts` is reported to the hooks along with
+    /// the `Enabled` event, see [`LifecycleEvent::current`].
+    pub(crate) fn restart_with_timeout(
+        &self,
+        timeout: Duration,
+        restarts: u32,
+    ) -> Result<HookHandle, HookError> {
+        self.spawn(Some(timeout), restarts)
+    }
+
+    fn spawn(&self, timeout: Option<Duration>, restarts: u32) -> Result<HookHandle, HookError> {
+        if self
+            .inner
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(HookError::already_running());
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        if !Self::attach(&self.inner, deadline, restarts) {
+            // Only a start with a timeout gives up on attaching.
+            return Err(HookError::timed_out(timeout.unwrap_or_default()));
+        }
+        let inner = self.inner.clone();
+        // The sender is dropped when the control thread exits, even if it panics,
+        // which lets the handle be awaited without blocking, see `HookHandle::wait_async`.
+        #[cfg(feature = "async")]
+        let (done_sender, done) = flume::bounded::<()>(0);
+        let control_thread = thread::spawn(move || {
+            #[cfg(feature = "async")]
+            let _done_sender = done_sender;
+            Self::control_thread_main(inner)
+        });
+        // After spawning the control thread, we dont want to return immediately because
+        // the user could attempt to post an event before the control and hook threads were properly
+        // initialized. We use the this condvar to wait until the control thread notifies us that
+        // it is initialized.
+        let (ref lock, ref cond) = self.inner.enabled;
+        let mut ready = lock.lock();
+        while !*ready {
+            match deadline {
+                Some(deadline) => {
+                    if cond.wait_until(&mut ready, deadline).timed_out() {
+                        break;
+                    }
+                }
+                None => cond.wait(&mut ready),
+            }
+        }
+        let enabled = *ready;
+        drop(ready);
+
+        let handle = HookHandle::new(
+            self.clone(),
+            control_thread,
+            #[cfg(feature = "async")]
+            done,
+        );
+
+        match timeout {
+            Some(timeout) if !enabled => {
+                // We do not wait for the backend that failed to start in time, the driver stops it once it
+                // is enabled. The control thread exits as soon as the session is detached, we wait for it
+                // so that the session can be started again right away.
+                self.inner.exit(Err(HookError::timed_out(timeout)));
+                driver::cancel(&self.inner);
+                let _ = handle.wait();
+                Err(HookError::timed_out(timeout))
+            }
+            // The control thread notifies the condvar when it exits, so the session might have
+            // failed before it was ever enabled.
+            Some(_) if !self.is_running() => match handle.wait() {
+                Err(err) => Err(err),
+                Ok(_) => Err(HookError::new(
+                    ErrorKind::Other,
+                    Operation::Start,
+                    "The hook stopped before it was enabled.",
+                )),
+            },
+            _ => Ok(handle),
+        }
+    }
+
+    /// Similar to [`start`] only it is blocking and uses the current thread as the control thread,
+    /// see [`hook_start_blocking`].
+    ///
+    /// If the session is already running this function will return immediately with an `Ok(())` value.
+    ///
+    /// [`start`]: HookSession::start
+    /// [`hook_start_blocking`]: crate::hook::global::hook_start_blocking
+    pub fn start_blocking(&self) -> Result<(), HookError> {
+        match self
+            .inner
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => {
+                Self::attach(&self.inner, None, 0);
+                Self::control_thread_main(self.inner.clone())
+            }
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Stop the session, the control thread of the session will finish quickly after.
+    ///
+    /// If this is the last running session the native hook is stopped as well,
+    /// and an error is returned if it could not be stopped. See [`hook_stop`].
+    ///
+    /// [`hook_stop`]: crate::hook::global::hook_stop
+    pub fn stop(&self) -> Result<(), HookError> {
+        self.inner.stop_requested.store(true, Ordering::SeqCst);
+        let res = driver::detach(&self.inner);
+        if res.is_err() {
+            // the session keeps running, so it was not stopped on purpose after all.
+            self.inner.stop_requested.store(false, Ordering::SeqCst);
+        }
+        res
+    }
+
+    /// Check if the last run of the session was stopped by a call to [`stop`],
+    /// as opposed to the native hook exiting on its own.
+    ///
+    /// [`stop`]: HookSession::stop
+    pub(crate) fn stop_requested(&self) -> bool {
+        self.inner.stop_requested.load(Ordering::SeqCst)
+    }
+
+    /// Register a hook handler with this session, see [`register_hook`].
+    ///
+    /// [`register_hook`]: crate::hook::global::register_hook
+    pub fn register_hook<F: Fn(&HookEvent) + Sync + Send + 'static>(&self, handler: F) -> HookId {
+        self.register_boxed_hook(Box::new(handler), HookOptions::default())
+    }
+
+    /// Register a hook handler with this session, that will be dispatched according to the
+    /// specified [`HookOptions`], see [`register_hook_with`].
+    ///
+    /// [`HookOptions`]: crate::hook::dispatch::HookOptions
+    /// [`register_hook_with`]: crate::hook::global::register_hook_with
+    pub fn register_hook_with<O, F>(&self, options: O, handler: F) -> HookId
+    where
+        O: Into<HookOptions>,
+        F: Fn(&HookEvent) + Sync + Send + 'static,
+    {
+        self.register_boxed_hook(Box::new(handler), options.into())
+    }
+
+    pub(crate) fn register_boxed_hook(
+        &self,
+        handler: HookCallback,
+        options: HookOptions,
+    ) -> HookId {
+        let id = next_hook_id();
+        self.register_boxed_hook_with_id(id, handler, options);
+        id
+    }
+
+    pub(crate) fn register_boxed_hook_with_id(
+        &self,
+        id: HookId,
+        handler: HookCallback,
+        options: HookOptions,
+    ) {
+        match options.mode {
+            DispatchMode::Dedicated => {
+                self.inner
+                    .workers
+                    .entry(id)
+                    .or_insert_with(|| Worker::spawn(Arc::downgrade(&self.inner), &self.inner.bus));
+            }
+            _ => {
+                // the hook might have been registered with a dedicated worker before.
+                self.inner.workers.remove(&id);
+            }
+        }
+
+        self.inner.insert_hook(
+            id,
+            RegisteredHook {
+                callback: handler,
+                options,
+                disabled: AtomicBool::new(false),
+                stats: CallbackCounter::default(),
+            },
+        );
+    }
+
+    /// Set what happens to a hook after it panics, the default policy is [`PanicPolicy::Disable`].
+    ///
+    /// The policy applies to the hooks called on worker threads as well, even while the session is stopping.
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use uiohook_rs::hook::dispatch::{DispatchMode, PanicPolicy};
+    /// use uiohook_rs::hook::session::HookSession;
+    /// use uiohook_rs::EventKind;
+    ///
+    /// let session = HookSession::new();
+    /// session.set_panic_policy(PanicPolicy::Remove);
+    ///
+    /// // the hook panics on its own thread while the session waits for it to see the `Disabled` event.
+    /// let id = session.register_hook_with(DispatchMode::Dedicated, |event| {
+    ///     if let EventKind::Disabled = event.kind {
+    ///         panic!("ahh!");
+    ///     }
+    /// });
+    ///
+    /// let handle = session.start().expect("oops session already running");
+    /// handle.stop().unwrap();
+    ///
+    /// // the hook was removed.
+    /// assert!(session.unregister_hook(id).is_none());
+    /// ```
+    ///
+    /// [`PanicPolicy::Disable`]: crate::hook::dispatch::PanicPolicy::Disable
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        *self.inner.panic_policy.lock() = policy;
+    }
+
+    /// Set a handler that is called with every panic caught while calling the hooks of this session.
+    /// Every call to this function **overwrites** the handler.
+    ///
+    /// The handler is called on the thread that called the panicking hook, after the [`PanicPolicy`] was applied.
+    /// Unlike the hooks, the handler is not isolated, so if it panics (for example, to continue the
+    /// hook's panic with [`std::panic::resume_unwind`]) the thread calling it will unwind.
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use uiohook_rs::hook::dispatch::PanicPolicy;
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::hook::session::HookSession;
+    /// use uiohook_rs::{EventKind, HookEvent};
+    ///
+    /// let session = HookSession::new();
+    /// let panics = Arc::new(AtomicUsize::new(0));
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let counter = panics.clone();
+    /// session.set_panic_policy(PanicPolicy::Remove);
+    /// session.on_panic(move |panic| {
+    ///     assert_eq!(panic.message, "ahh!");
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    /// });
+    ///
+    /// session.register_hook(|event| {
+    ///     if let EventKind::KeyPressed(_) = event.kind {
+    ///         panic!("ahh!");
+    ///     }
+    /// });
+    /// let counter = calls.clone();
+    /// session.register_hook(move |_| {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    /// });
+    ///
+    /// let handle = session.start().expect("oops session already running");
+    /// HookEvent::keyboard(Key::A).press().post().unwrap();
+    /// HookEvent::keyboard(Key::A).press().post().unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// // the panicking hook was removed after the first panic.
+    /// assert_eq!(panics.load(Ordering::SeqCst), 1);
+    /// // the other hook kept running: Enabled, 2 x KeyPressed, Disabled.
+    /// assert_eq!(calls.load(Ordering::SeqCst), 4);
+    /// ```
+    ///
+    /// [`PanicPolicy`]: crate::hook::dispatch::PanicPolicy
+    pub fn on_panic<F: Fn(HookPanic) + Sync + Send + 'static>(&self, handler: F) {
+        *self.inner.panic_handler.write() = Some(Box::new(handler));
+    }
+
+    /// Change the number of worker threads in the pool used by hooks registered with [`DispatchMode::Pool`],
+    /// the default size is [`DEFAULT_POOL_SIZE`].
+    ///
+    /// If the pool was already created, this function waits for the current workers to handle the events
+    /// dispatched to them, so the order of events is preserved for every hook.
+    ///
+    /// [`DispatchMode::Pool`]: crate::hook::dispatch::DispatchMode::Pool
+    /// [`DEFAULT_POOL_SIZE`]: crate::hook::dispatch::DEFAULT_POOL_SIZE
+    pub fn set_pool_size(&self, size: usize) {
+        let mut pool = self.inner.pool.lock();
+        self.inner.pool_size.store(size.max(1), Ordering::Relaxed);
+        match &*pool {
+            Some(current) if current.size() != size.max(1) => {
+                current.flush();
+                // the new pool is created on the next event for a pooled hook.
+                *pool = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Unregister a hook handler from this session, see [`unregister_hook`].
+    ///
+    /// [`unregister_hook`]: crate::hook::global::unregister_hook
+    pub fn unregister_hook(&self, hook_id: HookId) -> Option<HookCallback> {
+        self.inner.remove_hook(hook_id).map(|hook| hook.callback)
+    }
+
+    /// Exactly the same as [`unregister_hook`] except this function does not return anything.
+    ///
+    /// [`unregister_hook`]: HookSession::unregister_hook
+    pub fn drop_hook(&self, hook_id: HookId) {
+        self.inner.remove_hook(hook_id);
+    }
+
+    /// Create a [`Stream`] of all the events received by this session.
+    ///
+    /// The stream yields events received after it was created, and ends when the session is stopped.
+    /// See the [`stream`] module for more information.
+    ///
+    /// [`Stream`]: futures_core::Stream
+    /// [`stream`]: crate::hook::stream
+    #[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub fn events(&self) -> EventStream {
+        self.subscribe(None)
+    }
+
+    /// Similar to [`events`] only the stream yields just the events of the specified types.
+    ///
+    /// [`events`]: HookSession::events
+    #[cfg_attr(rustdoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub fn events_of<I: IntoIterator<Item = EventType>>(&self, types: I) -> EventStream {
+        self.subscribe(Some(types.into_iter().collect()))
+    }
+
+    #[cfg(feature = "async")]
+    fn subscribe(&self, filter: Option<Vec<EventType>>) -> EventStream {
+        let (stream, subscriber) = EventStream::new(filter);
+        self.inner.subscribers.lock().push(subscriber);
+        stream
+    }
+
+    /// Set the reserve filter of this session, see [`reserve_events`].
+    ///
+    /// Note that since the native hook is shared by all the running sessions, an event
+    /// reserved by one session is reserved for all of them.
+    ///
+    /// # Safety
+    /// See [`reserve_events`].
+    ///
+    /// [`reserve_events`]: crate::hook::global::reserve_events
+    #[cfg_attr(rustdoc, doc(cfg(any(target_os = "windows", target_os = "macos"))))]
+    #[cfg(any(rustdoc, target_os = "windows", target_os = "macos"))]
+    pub unsafe fn reserve_events<F: Fn(&HookEvent) -> bool + Sync + Send + 'static>(
+        &self,
+        filter: F,
+    ) {
+        *self.inner.reserve.lock() = Some(Box::new(filter));
+    }
+}
@@ -0,0 +1,469 @@
+//! Track the state of the input devices.
+//!
+//! Every session keeps track of the keys that are currently held, which is updated by the `control thread`
+//! from the [`KeyPressed`] and [`KeyReleased`] events before they are dispatched to the hooks.
+//! The state can be queried at any time with [`HookSession::keyboard_state`], though hooks should prefer
+//! [`KeyboardState::current`] which returns the state as it was right after the event they are handling,
+//! regardless of the thread the hook is called on and of the events received since.
+//!
+//! The state of the mouse is tracked the same way from the mouse events, see [`MouseState`],
+//! [`HookSession::mouse_state`] and [`MouseState::current`].
+//!
+//! Unlike the [`mask`] of the event, which the operating system only sets for modifier keys, the state
+//! includes every key that is held.
+//!
+//! The press events the operating system repeats while a key is held are marked with [`EventMode::REPEAT`]
+//! as the state is updated, see [`EventMetaData::is_repeat`].
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicBool, Ordering};
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::register_hook;
+//! use uiohook_rs::hook::state::KeyboardState;
+//! use uiohook_rs::{hook_start, EventKind, HookEvent};
+//!
+//! static CTRL_A: AtomicBool = AtomicBool::new(false);
+//!
+//! register_hook(|event| {
+//!     if let EventKind::KeyPressed(data) = &event.kind {
+//!         let keyboard = KeyboardState::current().unwrap();
+//!         if data.keycode == Key::A && keyboard.modifiers().ctrl() {
+//!             CTRL_A.store(true, Ordering::SeqCst);
+//!         }
+//!     }
+//! });
+//!
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::LeftControl).press().post().unwrap();
+//! HookEvent::keyboard(Key::A).pair().post().unwrap();
+//! HookEvent::keyboard(Key::LeftControl).release().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! assert!(CTRL_A.load(Ordering::SeqCst));
+//! ```
+//!
+//! [`KeyPressed`]: crate::hook::event::EventKind::KeyPressed
+//! [`KeyReleased`]: crate::hook::event::EventKind::KeyReleased
+//! [`HookSession::keyboard_state`]: crate::hook::session::HookSession::keyboard_state
+//! [`HookSession::mouse_state`]: crate::hook::session::HookSession::mouse_state
+//! [`mask`]: crate::hook::event::EventMetaData::mask
+//! [`EventMetaData::is_repeat`]: crate::hook::event::EventMetaData::is_repeat
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::hook::event::{now, EventKind, EventMode, HookEvent, Key, Modifiers, MouseButton};
+use crate::hook::supervisor::LifecycleEvent;
+use crate::system_properties;
+
+/// The delay before a held key is repeated when the system does not report it.
+pub(crate) const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(500);
+/// The interval between the repeated presses of a held key when the system does not report it.
+pub(crate) const DEFAULT_REPEAT_INTERVAL: Duration = Duration::from_millis(33);
+const STALE_MARGIN: u128 = 250;
+
+/// The keys held at some point in time, see the [module level documentation](crate::hook::state).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardState {
+    // The held keys in the order they were pressed.
+    pressed: Vec<HeldKey>,
+    locks: Modifiers,
+    repeat: RepeatTiming,
+}
+
+/// The key repeat settings of the system, they are queried when the session is enabled
+/// rather than for every repeated press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RepeatTiming {
+    delay: Duration,
+    interval: Duration,
+}
+
+impl Default for RepeatTiming {
+    fn default() -> Self {
+        RepeatTiming {
+            delay: DEFAULT_REPEAT_DELAY,
+            interval: DEFAULT_REPEAT_INTERVAL,
+        }
+    }
+}
+
+impl RepeatTiming {
+    fn current() -> Self {
+        RepeatTiming {
+            delay: system_properties::key_repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY),
+            interval: system_properties::key_repeat_interval().unwrap_or(DEFAULT_REPEAT_INTERVAL),
+        }
+    }
+
+    /// Check if a press of the held key at `time` is too late to be one of its repeated presses.
+    fn is_stale(&self, held: &HeldKey, time: u128) -> bool {
+        // The first repeat comes after the delay and the next ones at the interval.
+        let expected = if held.repeats == 0 {
+            self.delay
+        } else {
+            self.interval
+        };
+        // Events are not always delivered on time, so we allow for a generous margin.
+        time.saturating_sub(held.last) > expected.as_millis() * 2 + STALE_MARGIN
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HeldKey {
+    key: Key,
+    // The time of the first press event of the key.
+    time: u128,
+    // The time of the last press event, which is repeated while the key is held.
+    last: u128,
+    repeats: u32,
+}
+
+impl KeyboardState {
+    /// The keyboard state right after the event currently handled by the calling hook was received,
+    /// returns `None` when not called from a hook.
+    pub fn current() -> Option<Arc<KeyboardState>> {
+        CONTEXT.with(|context| {
+            context
+                .borrow()
+                .as_ref()
+                .map(|context| context.keyboard.clone())
+        })
+    }
+
+    /// Check if `key` is held.
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.pressed.iter().any(|held| held.key == key)
+    }
+
+    /// The held keys, in the order they were pressed.
+    pub fn pressed(&self) -> impl Iterator<Item = Key> + '_ {
+        self.pressed.iter().map(|held| held.key)
+    }
+
+    /// The held modifier keys, and the lock keys that are active.
+    ///
+    /// The state of the lock keys is taken from the mask of the last keyboard event,
+    /// since it might have been toggled before the session was started.
+    pub fn modifiers(&self) -> Modifiers {
+        self.pressed.iter().fold(self.locks, |modifiers, held| {
+            modifiers | Modifiers::from_key(held.key).keys()
+        })
+    }
+
+    /// The time `key` was pressed, as a unix timestamp in milliseconds like [`EventMetaData::time`],
+    /// returns `None` if the key is not held.
+    ///
+    /// [`EventMetaData::time`]: crate::hook::event::EventMetaData::time
+    pub fn pressed_at(&self, key: Key) -> Option<u128> {
+        self.held(key).map(|held| held.time)
+    }
+
+    /// The number of repeated press events received since `key` was pressed,
+    /// returns `None` if the key is not held.
+    ///
+    /// See [`EventMetaData::is_repeat`] for how repeated events are told apart.
+    ///
+    /// [`EventMetaData::is_repeat`]: crate::hook::event::EventMetaData::is_repeat
+    pub fn repeats(&self, key: Key) -> Option<u32> {
+        self.held(key).map(|held| held.repeats)
+    }
+
+    /// How long `key` has been held, returns `None` if the key is not held.
+    pub fn held_for(&self, key: Key) -> Option<Duration> {
+        let pressed_at = self.pressed_at(key)?;
+        Some(Duration::from_millis(
+            now().saturating_sub(pressed_at) as u64
+        ))
+    }
+
+    /// Check if the event affects the keyboard state.
+    fn tracks(event: &HookEvent) -> bool {
+        matches!(
+            event.kind,
+            EventKind::KeyPressed(_)
+                | EventKind::KeyReleased(_)
+                | EventKind::Enabled
+                | EventKind::Disabled
+        )
+    }
+
+    fn held(&self, key: Key) -> Option<&HeldKey> {
+        self.pressed.iter().find(|held| held.key == key)
+    }
+
+    /// Update the state from the event, returns `true` if the event is a repeated press of a held key.
+    fn update(&mut self, event: &HookEvent) -> bool {
+        let time = event.metadata.time;
+        let mut repeat = false;
+        match &event.kind {
+            // A held key generates repeated press events, we keep the time of the first one.
+            EventKind::KeyPressed(data) => {
+                match self
+                    .pressed
+                    .iter()
+                    .position(|held| held.key == data.keycode)
+                {
+                    Some(index) if !self.repeat.is_stale(&self.pressed[index], time) => {
+                        let held = &mut self.pressed[index];
+                        held.last = time;
+                        held.repeats += 1;
+                        repeat = true;
+                    }
+                    index => {
+                        // The release of a stale key was missed, it is pressed again.
+                        if let Some(index) = index {
+                            self.pressed.remove(index);
+                        }
+                        self.pressed.push(HeldKey {
+                            key: data.keycode,
+                            time,
+                            last: time,
+                            repeats: 0,
+                        });
+                    }
+                }
+            }
+            EventKind::KeyReleased(data) => {
+                self.pressed.retain(|held| held.key != data.keycode);
+            }
+            // the keys held when the session starts or stops are unknown,
+            // and the repeat settings might have changed while the session was stopped.
+            EventKind::Enabled => {
+                self.pressed.clear();
+                self.repeat = RepeatTiming::current();
+            }
+            EventKind::Disabled => self.pressed.clear(),
+            _ => (),
+        }
+
+        self.locks = event.metadata.mask.locks();
+        repeat
+    }
+}
+
+/// The state of the mouse at some point in time, see the [module level documentation](crate::hook::state).
+///
+/// # Example
+/// ```rust
+/// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+/// use std::sync::{Arc, Mutex};
+///
+/// use uiohook_rs::hook::event::MouseButton;
+/// use uiohook_rs::hook::session::HookSession;
+/// use uiohook_rs::hook::state::MouseState;
+/// use uiohook_rs::{EventKind, HookEvent};
+///
+/// let session = HookSession::new();
+/// let drags = Arc::new(Mutex::new(Vec::new()));
+/// let hook_drags = drags.clone();
+/// session.register_hook(move |event| {
+///     if let EventKind::MouseMoved(_) = event.kind {
+///         let mouse = MouseState::current().unwrap();
+///         if mouse.is_dragging() {
+///             hook_drags.lock().unwrap().push((mouse.drag_origin(), mouse.position()));
+///         }
+///     }
+/// });
+///
+/// let handle = session.start().expect("oops session already running");
+/// let left = || HookEvent::mouse(MouseButton::Left);
+/// left().with_position(10, 20).press().post().unwrap();
+/// left().moved(50, 60).post().unwrap();
+/// left().with_position(50, 60).release().post().unwrap();
+/// left().moved(70, 80).post().unwrap();
+/// handle.stop().unwrap();
+///
+/// // only the move made while the button was held is a drag.
+/// assert_eq!(*drags.lock().unwrap(), vec![(Some((10, 20)), Some((50, 60)))]);
+/// assert_eq!(session.mouse_state().position(), Some((70, 80)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MouseState {
+    position: Option<(i16, i16)>,
+    // The held buttons in the order they were pressed, along with the time they were pressed.
+    pressed: Vec<(MouseButton, u128)>,
+    drag_origin: Option<(i16, i16)>,
+    clicks: u16,
+}
+
+impl MouseState {
+    /// The mouse state right after the event currently handled by the calling hook was received,
+    /// returns `None` when not called from a hook.
+    pub fn current() -> Option<Arc<MouseState>> {
+        CONTEXT.with(|context| {
+            context
+                .borrow()
+                .as_ref()
+                .map(|context| context.mouse.clone())
+        })
+    }
+
+    /// The last known position of the cursor, returns `None` if no mouse event was received yet.
+    ///
+    /// Use [`cursor_position`] to query the display server for the position instead.
+    ///
+    /// [`cursor_position`]: crate::system_properties::cursor_position
+    pub fn position(&self) -> Option<(i16, i16)> {
+        self.position
+    }
+
+    /// Check if `button` is held.
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.pressed.iter().any(|(pressed, _)| *pressed == button)
+    }
+
+    /// The held buttons, in the order they were pressed.
+    pub fn pressed(&self) -> impl Iterator<Item = MouseButton> + '_ {
+        self.pressed.iter().map(|(button, _)| *button)
+    }
+
+    /// The held buttons as a mask.
+    pub fn buttons(&self) -> Modifiers {
+        self.pressed
+            .iter()
+            .fold(Modifiers::empty(), |buttons, (button, _)| {
+                buttons | Modifiers::from_button(*button)
+            })
+    }
+
+    /// The time `button` was pressed, as a unix timestamp in milliseconds like [`EventMetaData::time`],
+    /// returns `None` if the button is not held.
+    ///
+    /// [`EventMetaData::time`]: crate::hook::event::EventMetaData::time
+    pub fn pressed_at(&self, button: MouseButton) -> Option<u128> {
+        self.pressed
+            .iter()
+            .find(|(pressed, _)| *pressed == button)
+            .map(|(_, time)| *time)
+    }
+
+    /// The position where the first of the held buttons was pressed, returns `None` if no button is held.
+    pub fn drag_origin(&self) -> Option<(i16, i16)> {
+        self.drag_origin
+    }
+
+    /// Check if the cursor moved away from the drag origin while a button is held.
+    pub fn is_dragging(&self) -> bool {
+        self.drag_origin.is_some() && self.drag_origin != self.position
+    }
+
+    /// The click count of the last press or click, as reported by the operating system.
+    ///
+    /// Quick successive clicks increase the count, so a double click has a count of 2.
+    pub fn clicks(&self) -> u16 {
+        self.clicks
+    }
+
+    /// Check if the event affects the mouse state.
+    fn tracks(event: &HookEvent) -> bool {
+        !matches!(
+            event.kind,
+            EventKind::KeyTyped(_) | EventKind::KeyPressed(_) | EventKind::KeyReleased(_)
+        )
+    }
+
+    fn update(&mut self, event: &HookEvent) {
+        match &event.kind {
+            EventKind::MouseMoved(data) | EventKind::MouseDragged(data) => {
+                self.position = Some((data.x, data.y));
+            }
+            EventKind::MouseWheel(data) => self.position = Some((data.x, data.y)),
+            EventKind::MousePressed(data) => {
+                self.position = Some((data.x, data.y));
+                self.clicks = data.clicks;
+                if self.pressed.is_empty() {
+                    self.drag_origin = self.position;
+                }
+                if !self.is_pressed(data.button) {
+                    self.pressed.push((data.button, event.metadata.time));
+                }
+            }
+            EventKind::MouseReleased(data) => {
+                self.position = Some((data.x, data.y));
+                self.pressed.retain(|(button, _)| *button != data.button);
+                if self.pressed.is_empty() {
+                    self.drag_origin = None;
+                }
+            }
+            EventKind::MouseClicked(data) => {
+                self.position = Some((data.x, data.y));
+                self.clicks = data.clicks;
+            }
+            // the buttons held when the session starts or stops are unknown,
+            // though the last known position is still the best guess.
+            EventKind::Enabled | EventKind::Disabled => {
+                self.pressed.clear();
+                self.drag_origin = None;
+                self.clicks = 0;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The state of the input devices that goes along with an event as it is dispatched.
+#[derive(Clone, Default)]
+pub(crate) struct EventContext {
+    pub(crate) keyboard: Arc<KeyboardState>,
+    pub(crate) mouse: Arc<MouseState>,
+    // Only set for the `Enabled` and `Disabled` events.
+    pub(crate) lifecycle: Option<Lifecycle>,
+}
+
+/// The lifecycle event of a control event, it is duplicated along with the context.
+pub(crate) struct Lifecycle(pub(crate) LifecycleEvent);
+
+impl Clone for Lifecycle {
+    fn clone(&self) -> Self {
+        Lifecycle(self.0.duplicate())
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<EventContext>> = RefCell::new(None);
+}
+
+impl EventContext {
+    /// Update the tracked state from the event, and return the context to dispatch it with.
+    /// Repeated press events are marked with [`EventMode::REPEAT`].
+    pub(crate) fn track(&mut self, event: &mut HookEvent) -> EventContext {
+        // The state is shared with the contexts of the events that are still being dispatched,
+        // so it is only copied when it actually changes.
+        if KeyboardState::tracks(event) && Arc::make_mut(&mut self.keyboard).update(event) {
+            event.metadata.mode.insert(EventMode::REPEAT);
+        }
+        if MouseState::tracks(event) {
+            Arc::make_mut(&mut self.mouse).update(event);
+        }
+
+        self.clone()
+    }
+
+    /// The lifecycle event of the event currently handled by the calling hook.
+    pub(crate) fn current_lifecycle() -> Option<LifecycleEvent> {
+        CONTEXT.with(|context| {
+            let context = context.borrow();
+            Some(context.as_ref()?.lifecycle.as_ref()?.0.duplicate())
+        })
+    }
+
+    /// Call `f` with this context set as the context of the current thread.
+    pub(crate) fn enter<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        // The previous context is restored when the guard is dropped, even if `f` panics.
+        struct Guard(Option<EventContext>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CONTEXT.with(|context| *context.borrow_mut() = previous);
+            }
+        }
+
+        let _guard = Guard(CONTEXT.with(|context| context.replace(Some(self.clone()))));
+        f()
+    }
+}
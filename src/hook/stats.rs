@@ -0,0 +1,266 @@
+//! Runtime statistics of a session.
+//!
+//! Every session keeps track of how many events it received, how many events are waiting in its event bus,
+//! how many events were lost, how long it takes from the moment the operating system generated an event until
+//! the hooks are called with it, and how much time each hook spent handling events.
+//! A snapshot of these statistics is returned by [`HookSession::stats`], [`HookHandle::stats`] and [`hook_stats`],
+//! which is useful to find out whether hooks feel slow because the system is slow to deliver the events,
+//! because the event bus is backed up, or because one of the hooks takes too long.
+//!
+//! All the statistics are cumulative since the session was created, except for the queue depth,
+//! and they are kept across restarts of the session.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::thread::sleep;
+//! use std::time::Duration;
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::session::HookSession;
+//! use uiohook_rs::HookEvent;
+//!
+//! let session = HookSession::new();
+//! let slow = session.register_hook(|_| sleep(Duration::from_millis(2)));
+//!
+//! let handle = session.start().expect("oops session already running");
+//! HookEvent::keyboard(Key::A).pair().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! let stats = session.stats();
+//! assert_eq!(stats.events.key_pressed, 1);
+//! assert_eq!(stats.events.key_released, 1);
+//! assert_eq!(stats.queue_depth, 0);
+//! assert_eq!(stats.dropped, 0);
+//!
+//! // the hook was called with Enabled, KeyPressed, KeyReleased and Disabled.
+//! let hook = &stats.hooks[&slow];
+//! assert_eq!(hook.calls, 4);
+//! assert!(hook.total >= Duration::from_millis(8));
+//! ```
+//!
+//! [`HookSession::stats`]: crate::hook::session::HookSession::stats
+//! [`HookHandle::stats`]: crate::hook::global::HookHandle::stats
+//! [`hook_stats`]: crate::hook::global::hook_stats
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::hook::event::{now, EventKind, HookEvent};
+use crate::hook::global::HookId;
+
+/// The number of events of each kind received by a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventCounts {
+    pub enabled: u64,
+    pub disabled: u64,
+    pub key_typed: u64,
+    pub key_pressed: u64,
+    pub key_released: u64,
+    pub mouse_clicked: u64,
+    pub mouse_pressed: u64,
+    pub mouse_released: u64,
+    pub mouse_moved: u64,
+    pub mouse_dragged: u64,
+    pub mouse_wheel: u64,
+}
+
+impl EventCounts {
+    /// The number of events of the same kind as `kind` received by the session.
+    pub fn get(&self, kind: &EventKind) -> u64 {
+        match kind {
+            EventKind::Enabled => self.enabled,
+            EventKind::Disabled => self.disabled,
+            EventKind::KeyTyped(_) => self.key_typed,
+            EventKind::KeyPressed(_) => self.key_pressed,
+            EventKind::KeyReleased(_) => self.key_released,
+            EventKind::MouseClicked(_) => self.mouse_clicked,
+            EventKind::MousePressed(_) => self.mouse_pressed,
+            EventKind::MouseReleased(_) => self.mouse_released,
+            EventKind::MouseMoved(_) => self.mouse_moved,
+            EventKind::MouseDragged(_) => self.mouse_dragged,
+            EventKind::MouseWheel(_) => self.mouse_wheel,
+        }
+    }
+
+    /// The total number of events received by the session.
+    pub fn total(&self) -> u64 {
+        self.enabled
+            + self.disabled
+            + self.key_typed
+            + self.key_pressed
+            + self.key_released
+            + self.mouse_clicked
+            + self.mouse_pressed
+            + self.mouse_released
+            + self.mouse_moved
+            + self.mouse_dragged
+            + self.mouse_wheel
+    }
+}
+
+/// The time it took from the moment an event was generated (see [`EventMetaData::time`])
+/// until a hook was called with it.
+///
+/// Control events, and events without a timestamp are not measured.
+///
+/// [`EventMetaData::time`]: crate::hook::event::EventMetaData::time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The number of measured hook calls.
+    pub samples: u64,
+    /// The sum of all the measured latencies.
+    pub total: Duration,
+    /// The longest measured latency.
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// The average latency, or zero if nothing was measured.
+    pub fn mean(&self) -> Duration {
+        match self.samples {
+            0 => Duration::default(),
+            samples => Duration::from_nanos((self.total.as_nanos() / samples as u128) as u64),
+        }
+    }
+}
+
+/// The time a single hook spent handling events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallbackStats {
+    /// The number of times the hook was called.
+    pub calls: u64,
+    /// The time spent inside the hook over all the calls.
+    pub total: Duration,
+}
+
+impl CallbackStats {
+    /// The average time of a single call, or zero if the hook was never called.
+    pub fn mean(&self) -> Duration {
+        match self.calls {
+            0 => Duration::default(),
+            calls => Duration::from_nanos((self.total.as_nanos() / calls as u128) as u64),
+        }
+    }
+}
+
+/// A snapshot of the statistics of a session, see the [module level documentation](crate::hook::stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookStats {
+    /// The number of events of each kind received by the session.
+    pub events: EventCounts,
+    /// The number of events waiting in the event bus when the snapshot was taken.
+    pub queue_depth: usize,
+    /// The number of events lost because the event bus or the queue of a worker was full,
+    /// see [`HookSession::dropped_events`].
+    ///
+    /// [`HookSession::dropped_events`]: crate::hook::session::HookSession::dropped_events
+    pub dropped: u64,
+    /// The time it took for events to reach the hooks.
+    pub latency: LatencyStats,
+    /// The time spent by each of the currently registered hooks.
+    pub hooks: HashMap<HookId, CallbackStats>,
+}
+
+#[derive(Default)]
+pub(crate) struct CallbackCounter {
+    calls: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl CallbackCounter {
+    pub(crate) fn record(&self, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> CallbackStats {
+        CallbackStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+const KIND_COUNT: usize = 11;
+
+fn kind_index(kind: &EventKind) -> usize {
+    match kind {
+        EventKind::Enabled => 0,
+        EventKind::Disabled => 1,
+        EventKind::KeyTyped(_) => 2,
+        EventKind::KeyPressed(_) => 3,
+        EventKind::KeyReleased(_) => 4,
+        EventKind::MouseClicked(_) => 5,
+        EventKind::MousePressed(_) => 6,
+        EventKind::MouseReleased(_) => 7,
+        EventKind::MouseMoved(_) => 8,
+        EventKind::MouseDragged(_) => 9,
+        EventKind::MouseWheel(_) => 10,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SessionStats {
+    events: [AtomicU64; KIND_COUNT],
+    latency_samples: AtomicU64,
+    latency_total: AtomicU64,
+    latency_max: AtomicU64,
+}
+
+impl SessionStats {
+    pub(crate) fn record_event(&self, event: &HookEvent) {
+        self.events[kind_index(&event.kind)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the latency of an event that is about to be handled by a hook.
+    pub(crate) fn record_latency(&self, event: &HookEvent) {
+        if event.metadata.time == 0
+            || matches!(event.kind, EventKind::Enabled | EventKind::Disabled)
+        {
+            return;
+        }
+
+        // The timestamp only has a millisecond resolution, and the clock might have been
+        // adjusted since the event was generated, so the latency is clamped to zero.
+        let latency = (now().saturating_sub(event.metadata.time) * 1_000_000) as u64;
+
+        self.latency_samples.fetch_add(1, Ordering::Relaxed);
+        self.latency_total.fetch_add(latency, Ordering::Relaxed);
+        self.latency_max.fetch_max(latency, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(
+        &self,
+        queue_depth: usize,
+        dropped: u64,
+        hooks: HashMap<HookId, CallbackStats>,
+    ) -> HookStats {
+        let count = |index: usize| self.events[index].load(Ordering::Relaxed);
+        HookStats {
+            events: EventCounts {
+                enabled: count(0),
+                disabled: count(1),
+                key_typed: count(2),
+                key_pressed: count(3),
+                key_released: count(4),
+                mouse_clicked: count(5),
+                mouse_pressed: count(6),
+                mouse_released: count(7),
+                mouse_moved: count(8),
+                mouse_dragged: count(9),
+                mouse_wheel: count(10),
+            },
+            queue_depth,
+            dropped,
+            latency: LatencyStats {
+                samples: self.latency_samples.load(Ordering::Relaxed),
+                total: Duration::from_nanos(self.latency_total.load(Ordering::Relaxed)),
+                max: Duration::from_nanos(self.latency_max.load(Ordering::Relaxed)),
+            },
+            hooks,
+        }
+    }
+}
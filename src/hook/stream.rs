@@ -0,0 +1,108 @@
+//! Asynchronous access to hook events.
+//!
+//! This module is only available with the `async` feature, it provides an [`EventStream`]
+//! implementing [`futures_core::Stream`], so that hook events can be consumed from an async runtime
+//! without bridging [`register_hook`] callbacks through channels by hand.
+//!
+//! Each stream is fed by the `control thread` of the session it was created from, right after the
+//! registered hooks are called for the event. The stream ends once the session is stopped,
+//! after yielding the [`Disabled`] event (if control events were not filtered out).
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use futures::executor::block_on;
+//! use futures::StreamExt;
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::{events_of, hook_stop};
+//! use uiohook_rs::{hook_start, EventKind, EventType, HookEvent};
+//!
+//! // only keyboard events will be yielded by the stream.
+//! let mut keys = events_of([EventType::Keyboard]);
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::A).pair().post().unwrap();
+//!
+//! block_on(async move {
+//!     let pressed = keys.next().await.unwrap();
+//!     assert!(matches!(pressed.kind, EventKind::KeyPressed(_)));
+//!     let released = keys.next().await.unwrap();
+//!     assert!(matches!(released.kind, EventKind::KeyReleased(_)));
+//!
+//!     hook_stop().unwrap();
+//!     handle.wait_async().await.unwrap();
+//!     // the stream ends once the hook is stopped.
+//!     assert!(keys.next().await.is_none());
+//! });
+//! ```
+//!
+//! [`register_hook`]: crate::hook::global::register_hook
+//! [`Disabled`]: crate::hook::event::EventKind::Disabled
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use flume::r#async::RecvStream;
+use flume::{unbounded, Sender};
+use futures_core::Stream;
+
+use crate::hook::event::{EventType, HookEvent};
+
+pub(crate) struct Subscriber {
+    filter: Option<Vec<EventType>>,
+    sender: Sender<HookEvent>,
+}
+
+impl Subscriber {
+    /// Send the event to the stream if it passes the filter,
+    /// returns `false` if the stream was dropped and the subscriber can be removed.
+    pub(crate) fn publish(&self, event: &HookEvent) -> bool {
+        if let Some(filter) = &self.filter {
+            if !filter.contains(&event.get_type()) {
+                return !self.sender.is_disconnected();
+            }
+        }
+
+        self.sender.send(event.clone()).is_ok()
+    }
+}
+
+/// A [`Stream`] of the events received by a session.
+///
+/// Created by [`HookSession::events`] and [`HookSession::events_of`], or the [`events`]
+/// and [`events_of`] functions for the default session.
+///
+/// The stream buffers every event it receives until it is polled, so a stream that is not polled
+/// should be dropped to avoid accumulating events.
+///
+/// [`HookSession::events`]: crate::hook::session::HookSession::events
+/// [`HookSession::events_of`]: crate::hook::session::HookSession::events_of
+/// [`events`]: crate::hook::global::events
+/// [`events_of`]: crate::hook::global::events_of
+pub struct EventStream {
+    receiver: RecvStream<'static, HookEvent>,
+}
+
+impl EventStream {
+    pub(crate) fn new(filter: Option<Vec<EventType>>) -> (Self, Subscriber) {
+        let (sender, receiver) = unbounded();
+        (
+            EventStream {
+                receiver: receiver.into_stream(),
+            },
+            Subscriber { filter, sender },
+        )
+    }
+}
+
+impl Stream for EventStream {
+    type Item = HookEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.receiver.size_hint()
+    }
+}
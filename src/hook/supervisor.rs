@@ -0,0 +1,420 @@
+//! Keep a session running through failures of the native hook.
+//!
+//! The native hook might stop on its own, for example when the connection to the X server is lost, in which
+//! case every running session receives a [`Disabled`] event and stops, with the error of the native hook
+//! available through [`HookHandle::wait`]. A [`Supervisor`] watches a session and restarts it whenever it
+//! stops without being asked to, waiting between restart attempts according to a [`Backoff`].
+//!
+//! The registered hooks belong to the session, so they survive the restart, and like any other start of the
+//! session they receive an [`Enabled`] event when the native hook is back up. Listeners registered with
+//! [`Supervisor::on_lifecycle`] are notified with a [`LifecycleEvent`] whenever the supervised session
+//! starts, fails or is being restarted. The hooks themselves get the lifecycle event that goes along with
+//! the [`Enabled`] and [`Disabled`] events they handle through [`LifecycleEvent::current`], which tells a
+//! restart from a first start, and a failure from a stop.
+//!
+//! The supervisor stops once it is stopped through its [`SupervisorHandle`], or when the session itself is
+//! stopped, for example by calling [`hook_stop`].
+//!
+//! # Example
+//! ```rust
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::mpsc::channel;
+//! use std::sync::Mutex;
+//! use std::time::Duration;
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::register_hook;
+//! use uiohook_rs::hook::supervisor::{Backoff, LifecycleEvent, Supervisor};
+//! use uiohook_rs::testing::VirtualDevice;
+//! use uiohook_rs::{ErrorKind, EventKind, HookError, HookEvent, Operation};
+//!
+//! static PRESSED: AtomicUsize = AtomicUsize::new(0);
+//! static RESTARTED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! let device = VirtualDevice::new().with_loopback();
+//! device.install();
+//!
+//! register_hook(|event| match event.kind {
+//!     EventKind::KeyPressed(_) => {
+//!         PRESSED.fetch_add(1, Ordering::SeqCst);
+//!     }
+//!     EventKind::Enabled => {
+//!         // the hook can tell the restart from the first start.
+//!         let lifecycle = LifecycleEvent::current();
+//!         if matches!(lifecycle, Some(LifecycleEvent::Started { restarts }) if restarts > 0) {
+//!             RESTARTED.fetch_add(1, Ordering::SeqCst);
+//!         }
+//!     }
+//!     _ => (),
+//! });
+//!
+//! let (sender, started) = channel();
+//! let sender = Mutex::new(sender);
+//! let handle = Supervisor::new()
+//!     .with_backoff(Backoff::constant(Duration::from_millis(10)))
+//!     .on_lifecycle(move |event| {
+//!         if let LifecycleEvent::Started { restarts } = event {
+//!             sender.lock().unwrap().send(*restarts).unwrap();
+//!         }
+//!     })
+//!     .start()
+//!     .expect("oops hook did not start");
+//! assert_eq!(started.recv().unwrap(), 0);
+//!
+//! // the connection to the display server is lost...
+//! device.fail(HookError::new(
+//!     ErrorKind::DisplayUnavailable,
+//!     Operation::Run,
+//!     "lost the display",
+//! ));
+//! assert_eq!(started.recv().unwrap(), 1);
+//!
+//! // the hook is still registered after the restart.
+//! HookEvent::keyboard(Key::A).press().post().unwrap();
+//! handle.stop().unwrap();
+//! assert_eq!(PRESSED.load(Ordering::SeqCst), 1);
+//! assert_eq!(RESTARTED.load(Ordering::SeqCst), 1);
+//! ```
+//!
+//! [`Disabled`]: crate::hook::event::EventKind::Disabled
+//! [`Enabled`]: crate::hook::event::EventKind::Enabled
+//! [`HookHandle::wait`]: crate::hook::global::HookHandle::wait
+//! [`hook_stop`]: crate::hook::global::hook_stop
+
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::{const_mutex, Condvar, Mutex};
+
+use crate::error::HookError;
+use crate::hook::global::HookHandle;
+use crate::hook::session::HookSession;
+use crate::hook::state::EventContext;
+
+type LifecycleListener = Box<dyn Fn(&LifecycleEvent) + Sync + Send>;
+
+/// How long to wait before each restart attempt.
+///
+/// The delay starts at the initial delay, and is multiplied by the factor after every failed attempt,
+/// up to the maximum delay. The default backoff starts at 100 milliseconds and doubles up to 30 seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    factor: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    /// A backoff that starts at `initial` and doubles after every failed attempt, up to `max`.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max: max.max(initial),
+            factor: 2,
+        }
+    }
+
+    /// A backoff that always waits `delay` before restarting.
+    pub fn constant(delay: Duration) -> Self {
+        Backoff::new(delay, delay)
+    }
+
+    /// Set the factor the delay is multiplied by after every failed attempt.
+    pub fn with_factor(mut self, factor: u32) -> Self {
+        self.factor = factor.max(1);
+        self
+    }
+
+    /// The delay before the specified restart attempt, the first attempt is 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let mut delay = self.initial;
+        for _ in 1..attempt {
+            if delay >= self.max {
+                break;
+            }
+            delay = delay.saturating_mul(self.factor);
+        }
+        delay.min(self.max)
+    }
+}
+
+/// A change in the lifecycle of a supervised session, see [`Supervisor::on_lifecycle`].
+#[derive(Debug)]
+pub enum LifecycleEvent {
+    /// The native hook was enabled, `restarts` is the number of times the session was restarted so far.
+    Started { restarts: u32 },
+    /// The session stopped without being asked to, or failed to restart.
+    ///
+    /// The error is `None` if the native hook stopped on its own without reporting an error.
+    /// Note that the panic payload of errors of kind [`ErrorKind::Panic`] is not included.
+    ///
+    /// [`ErrorKind::Panic`]: crate::ErrorKind::Panic
+    Failed { error: Option<HookError> },
+    /// The session will be restarted after `delay`.
+    Restarting { attempt: u32, delay: Duration },
+    /// The session failed more times in a row than allowed by [`Supervisor::with_max_attempts`],
+    /// and will not be restarted.
+    GaveUp,
+    /// The session was stopped on purpose and the supervisor exited.
+    Stopped,
+}
+
+impl LifecycleEvent {
+    /// The lifecycle event that goes along with the [`Enabled`] or [`Disabled`] event currently handled
+    /// by the calling hook, returns `None` for any other event, or when not called from a hook.
+    ///
+    /// Hooks receive the lifecycle events of every session, supervised or not:
+    ///
+    /// *   [`Enabled`] comes with [`Started`], where `restarts` is the number of times a [`Supervisor`]
+    ///     restarted the session since it was started, so it is `0` for the first start.
+    /// *   [`Disabled`] comes with [`Stopped`] if the session was stopped on purpose, and with [`Failed`]
+    ///     otherwise, along with the error of the native hook if it reported one.
+    ///
+    /// The other lifecycle events happen while the session is not running, and are only delivered to the
+    /// listeners of the supervisor, see [`Supervisor::on_lifecycle`].
+    ///
+    /// [`Enabled`]: crate::hook::event::EventKind::Enabled
+    /// [`Disabled`]: crate::hook::event::EventKind::Disabled
+    /// [`Started`]: LifecycleEvent::Started
+    /// [`Stopped`]: LifecycleEvent::Stopped
+    /// [`Failed`]: LifecycleEvent::Failed
+    pub fn current() -> Option<LifecycleEvent> {
+        EventContext::current_lifecycle()
+    }
+
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            LifecycleEvent::Started { restarts } => LifecycleEvent::Started {
+                restarts: *restarts,
+            },
+            LifecycleEvent::Failed { error } => LifecycleEvent::Failed {
+                error: error.as_ref().map(HookError::duplicate),
+            },
+            LifecycleEvent::Restarting { attempt, delay } => LifecycleEvent::Restarting {
+                attempt: *attempt,
+                delay: *delay,
+            },
+            LifecycleEvent::GaveUp => LifecycleEvent::GaveUp,
+            LifecycleEvent::Stopped => LifecycleEvent::Stopped,
+        }
+    }
+}
+
+/// Restarts a session whenever the native hook stops unexpectedly,
+/// see the [module level documentation](crate::hook::supervisor).
+pub struct Supervisor {
+    session: HookSession,
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+    start_timeout: Duration,
+    listeners: Vec<LifecycleListener>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    /// Create a supervisor for the default session, see [`HookSession::global`].
+    pub fn new() -> Self {
+        Self::for_session(HookSession::global())
+    }
+
+    /// Create a supervisor for the specified session.
+    pub fn for_session(session: &HookSession) -> Self {
+        Supervisor {
+            session: session.clone(),
+            backoff: Backoff::default(),
+            max_attempts: None,
+            start_timeout: Duration::from_secs(5),
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Set the delay between restart attempts, see [`Backoff`].
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Give up after `attempts` restart attempts in a row, by default the supervisor never gives up.
+    ///
+    /// The number of attempts is reset once the session stays up for at least the maximum delay of the backoff.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Set how long to wait for the native hook to be enabled when restarting, the default is 5 seconds.
+    /// See [`HookSession::start_with_timeout`].
+    pub fn with_start_timeout(mut self, timeout: Duration) -> Self {
+        self.start_timeout = timeout;
+        self
+    }
+
+    /// Add a listener that is notified with every [`LifecycleEvent`] of the supervised session.
+    ///
+    /// The listeners are called on the thread that started the supervisor for the first [`Started`] event,
+    /// and on the supervisor thread for all the other events.
+    ///
+    /// [`Started`]: LifecycleEvent::Started
+    pub fn on_lifecycle<F: Fn(&LifecycleEvent) + Sync + Send + 'static>(
+        mut self,
+        listener: F,
+    ) -> Self {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Start the session and begin supervising it.
+    ///
+    /// The first start is not retried, if the session cannot be started the error is returned
+    /// right away, see [`HookSession::start_with_timeout`].
+    pub fn start(self) -> Result<SupervisorHandle, HookError> {
+        let handle = self.session.start_with_timeout(self.start_timeout)?;
+        self.notify(&LifecycleEvent::Started { restarts: 0 });
+
+        let shared = Arc::new(Shared {
+            stopping: const_mutex(false),
+            cond: Condvar::new(),
+        });
+        let session = self.session.clone();
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || self.supervise(&thread_shared, handle));
+
+        Ok(SupervisorHandle {
+            session,
+            shared,
+            thread,
+        })
+    }
+
+    fn notify(&self, event: &LifecycleEvent) {
+        for listener in &self.listeners {
+            listener(event);
+        }
+    }
+
+    fn supervise(self, shared: &Shared, mut handle: HookHandle) -> Result<(), HookError> {
+        let mut restarts = 0;
+        let mut attempt = 0;
+
+        loop {
+            let started_at = Instant::now();
+            let result = handle.wait();
+            if self.session.stop_requested() || shared.is_stopping() {
+                self.notify(&LifecycleEvent::Stopped);
+                return result;
+            }
+            if started_at.elapsed() >= self.backoff.max {
+                attempt = 0;
+            }
+
+            let mut error = result.err();
+            handle = loop {
+                self.notify(&LifecycleEvent::Failed {
+                    error: error.as_ref().map(HookError::duplicate),
+                });
+
+                attempt += 1;
+                if matches!(self.max_attempts, Some(max) if attempt > max) {
+                    self.notify(&LifecycleEvent::GaveUp);
+                    return match error {
+                        Some(error) => Err(error),
+                        None => Ok(()),
+                    };
+                }
+
+                let delay = self.backoff.delay(attempt);
+                self.notify(&LifecycleEvent::Restarting { attempt, delay });
+                if shared.sleep(delay) {
+                    self.notify(&LifecycleEvent::Stopped);
+                    return Ok(());
+                }
+
+                match self
+                    .session
+                    .restart_with_timeout(self.start_timeout, restarts + 1)
+                {
+                    Ok(handle) => break handle,
+                    Err(err) => error = Some(err),
+                }
+            };
+
+            // The supervisor might have been stopped while the session was restarting,
+            // in which case the stop request did not reach the new run.
+            if shared.is_stopping() {
+                let result = handle.stop();
+                self.notify(&LifecycleEvent::Stopped);
+                return result;
+            }
+
+            restarts += 1;
+            self.notify(&LifecycleEvent::Started { restarts });
+        }
+    }
+}
+
+struct Shared {
+    stopping: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn is_stopping(&self) -> bool {
+        *self.stopping.lock()
+    }
+
+    /// Sleep for `delay`, or until the supervisor is stopped, returns `true` if it was stopped.
+    fn sleep(&self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        let mut stopping = self.stopping.lock();
+        while !*stopping {
+            if self.cond.wait_until(&mut stopping, deadline).timed_out() {
+                break;
+            }
+        }
+        *stopping
+    }
+}
+
+/// A handle to a running [`Supervisor`].
+pub struct SupervisorHandle {
+    session: HookSession,
+    shared: Arc<Shared>,
+    thread: JoinHandle<Result<(), HookError>>,
+}
+
+impl SupervisorHandle {
+    /// Wait for the supervisor to exit.
+    ///
+    /// The supervisor exits once the session is stopped on purpose, returning the result of the last run,
+    /// or once it gives up restarting the session, returning the last error.
+    pub fn wait(self) -> Result<(), HookError> {
+        self.thread.join()?
+    }
+
+    /// Stop the supervised session and wait for the supervisor to exit.
+    ///
+    /// If the session could not be stopped the error is returned immediately,
+    /// and the supervisor will exit once the session is stopped.
+    pub fn stop(self) -> Result<(), HookError> {
+        {
+            let mut stopping = self.shared.stopping.lock();
+            *stopping = true;
+            self.shared.cond.notify_all();
+        }
+
+        self.session.stop()?;
+        self.wait()
+    }
+}
@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+use crate::hotkey::sequence::Sequence;
+
+/// An error returned when parsing a [`Hotkey`] from an accelerator string like `"Ctrl+Shift+K"`.
+///
+/// [`Hotkey`]: crate::hotkey::Hotkey
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseHotkeyError {
+    /// The string is empty, or there is nothing between two of its `+` or `,` separators.
+    #[error("The hotkey or one of its parts is empty")]
+    Empty,
+    /// A part of the string is neither a key, a mouse button nor a modifier.
+    #[error("Unknown key or modifier `{0}`")]
+    UnknownName(String),
+    /// A key or a mouse button appears before the last part of the string,
+    /// only the last part can be a key or a button.
+    #[error(
+        "`{0}` is not a modifier, only the last part of a hotkey can be a key or a mouse button"
+    )]
+    NotAModifier(String),
+    /// The last part of the string is a modifier, or is missing.
+    #[error("The hotkey has no key or mouse button after its modifiers")]
+    MissingKey,
+    /// The same modifier appears more than once, possibly for different sides of the keyboard.
+    #[error("The modifier `{0}` appears more than once")]
+    DuplicateModifier(String),
+}
+
+/// An error returned when registering a hotkey or a sequence of hotkeys that overlaps one that is already
+/// registered, see [`Sequence::overlaps`] and [`HotkeyManager::register_sequence`].
+///
+/// [`Sequence::overlaps`]: crate::hotkey::sequence::Sequence::overlaps
+/// [`HotkeyManager::register_sequence`]: crate::hotkey::manager::HotkeyManager::register_sequence
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("The hotkey {sequence} overlaps the registered hotkey {existing}")]
+pub struct HotkeyConflict {
+    /// The hotkey that was being registered, hotkeys of a single stroke are sequences of length one.
+    pub sequence: Sequence,
+    /// The registered hotkey it overlaps.
+    pub existing: Sequence,
+}
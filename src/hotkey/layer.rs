@@ -0,0 +1,206 @@
+//! Group hotkeys into layers, like the modes of vim or the binding modes of i3.
+//!
+//! Every hotkey and sequence registered with a [`HotkeyManager`] belongs to a layer, and only the bindings
+//! of the active layer are activated. The hotkeys registered directly with the manager belong to the
+//! [`DEFAULT_LAYER`], which is active when the manager is created. Layers are referred to by name and do not
+//! need to be created, use [`HotkeyManager::layer`] to register hotkeys in a layer.
+//!
+//! The active layer is changed with [`HotkeyManager::switch_layer`], or by the hotkeys registered with
+//! [`Layer::register_switch`]. Layers are [`Persistent`] by default, they stay active until another layer
+//! is activated. A [`Transient`] layer on the other hand returns to the last persistent layer once one of its
+//! hotkeys is activated, when a key or a mouse button that does not belong to any of its hotkeys is pressed,
+//! or when its timeout elapses. A hotkey that switches to a transient layer makes a leader key.
+//!
+//! Sequences in progress are cancelled whenever the active layer changes, and listeners registered with
+//! [`HotkeyManager::on_layer_change`] are notified with the name of the new active layer.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::time::Duration;
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hotkey::layer::{LayerMode, DEFAULT_LAYER};
+//! use uiohook_rs::hotkey::manager::HotkeyManager;
+//! use uiohook_rs::{hook_start, HookEvent};
+//!
+//! static SAVED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! let manager = HotkeyManager::new();
+//! // `Ctrl+Space` is the leader key, which enters the command layer for a single command.
+//! manager
+//!     .layer(DEFAULT_LAYER)
+//!     .register_switch("Ctrl+Space".parse().unwrap(), "command")
+//!     .unwrap();
+//! let command = manager.layer("command");
+//! command.set_mode(LayerMode::Transient {
+//!     timeout: Some(Duration::from_secs(2)),
+//! });
+//! command
+//!     .register("S".parse().unwrap(), |_| {
+//!         SAVED.fetch_add(1, Ordering::SeqCst);
+//!     })
+//!     .unwrap();
+//!
+//! let leader = || {
+//!     HookEvent::keyboard(Key::LeftControl).press().post().unwrap();
+//!     HookEvent::keyboard(Key::Space).pair().post().unwrap();
+//!     HookEvent::keyboard(Key::LeftControl).release().post().unwrap();
+//! };
+//! let handle = hook_start().expect("oops hook already running");
+//! // `S` only saves right after the leader key.
+//! HookEvent::keyboard(Key::S).pair().post().unwrap();
+//! leader();
+//! HookEvent::keyboard(Key::S).pair().post().unwrap();
+//! HookEvent::keyboard(Key::S).pair().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! assert_eq!(SAVED.load(Ordering::SeqCst), 1);
+//! assert_eq!(manager.active_layer(), DEFAULT_LAYER);
+//! ```
+//!
+//! [`HotkeyManager`]: crate::hotkey::manager::HotkeyManager
+//! [`HotkeyManager::layer`]: crate::hotkey::manager::HotkeyManager::layer
+//! [`HotkeyManager::switch_layer`]: crate::hotkey::manager::HotkeyManager::switch_layer
+//! [`HotkeyManager::on_layer_change`]: crate::hotkey::manager::HotkeyManager::on_layer_change
+//! [`Persistent`]: LayerMode::Persistent
+//! [`Transient`]: LayerMode::Transient
+
+use std::time::Duration;
+
+use crate::hook::event::HookEvent;
+use crate::hotkey::manager::{Command, HotkeyManager};
+use crate::hotkey::sequence::Sequence;
+use crate::hotkey::Hotkey;
+use crate::hotkey::HotkeyConflict;
+
+/// The name of the layer that is active when a [`HotkeyManager`] is created.
+///
+/// [`HotkeyManager`]: crate::hotkey::manager::HotkeyManager
+pub const DEFAULT_LAYER: &str = "default";
+
+/// How long a layer stays active, see the [module level documentation](crate::hotkey::layer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerMode {
+    /// The layer stays active until another layer is activated.
+    Persistent,
+    /// The layer returns to the last persistent layer after a single hotkey, after any other press,
+    /// or once `timeout` elapses since it was activated. Like the timeouts of sequences, the timeout
+    /// is checked as the events arrive.
+    Transient { timeout: Option<Duration> },
+}
+
+impl Default for LayerMode {
+    fn default() -> Self {
+        LayerMode::Persistent
+    }
+}
+
+/// A named layer of a [`HotkeyManager`], see the [module level documentation](crate::hotkey::layer).
+///
+/// [`HotkeyManager`]: crate::hotkey::manager::HotkeyManager
+pub struct Layer<'a> {
+    manager: &'a HotkeyManager,
+    name: String,
+}
+
+impl<'a> Layer<'a> {
+    pub(crate) fn new(manager: &'a HotkeyManager, name: &str) -> Self {
+        Layer {
+            manager,
+            name: name.to_string(),
+        }
+    }
+
+    /// The name of the layer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Register a hotkey in this layer, see [`HotkeyManager::register`].
+    ///
+    /// Hotkeys only conflict with the hotkeys of their own layer.
+    ///
+    /// [`HotkeyManager::register`]: crate::hotkey::manager::HotkeyManager::register
+    pub fn register<F>(&self, hotkey: Hotkey, callback: F) -> Result<(), HotkeyConflict>
+    where
+        F: Fn(&HookEvent) + Sync + Send + 'static,
+    {
+        self.register_sequence(hotkey.into(), callback)
+    }
+
+    /// Register a sequence of hotkeys in this layer, see [`HotkeyManager::register_sequence`].
+    ///
+    /// [`HotkeyManager::register_sequence`]: crate::hotkey::manager::HotkeyManager::register_sequence
+    pub fn register_sequence<F>(
+        &self,
+        sequence: Sequence,
+        callback: F,
+    ) -> Result<(), HotkeyConflict>
+    where
+        F: Fn(&HookEvent) + Sync + Send + 'static,
+    {
+        self.manager
+            .bind(&self.name, sequence, Command::call(callback))
+    }
+
+    /// Register a hotkey or a sequence in this layer that makes `target` the active layer.
+    pub fn register_switch(&self, sequence: Sequence, target: &str) -> Result<(), HotkeyConflict> {
+        self.manager
+            .bind(&self.name, sequence, Command::Switch(target.to_string()))
+    }
+
+    /// Unregister a hotkey from this layer, returns `false` if the hotkey was not registered.
+    pub fn unregister(&self, hotkey: &Hotkey) -> bool {
+        self.unregister_sequence(&Sequence::from(*hotkey))
+    }
+
+    /// Unregister a sequence or a layer switch from this layer, returns `false` if it was not registered.
+    pub fn unregister_sequence(&self, sequence: &Sequence) -> bool {
+        self.manager.unbind(&self.name, sequence)
+    }
+
+    /// The hotkeys and sequences registered in this layer that overlap `sequence`.
+    pub fn conflicts(&self, sequence: &Sequence) -> Vec<Sequence> {
+        self.manager
+            .bound(&self.name, |bound| bound.overlaps(sequence))
+    }
+
+    /// The hotkeys registered in this layer, in the order they were registered.
+    pub fn hotkeys(&self) -> Vec<Hotkey> {
+        self.manager
+            .bound(&self.name, |bound| bound.strokes().len() == 1)
+            .iter()
+            .map(|sequence| sequence.strokes()[0])
+            .collect()
+    }
+
+    /// The sequences of more than one stroke registered in this layer, in the order they were registered.
+    pub fn sequences(&self) -> Vec<Sequence> {
+        self.manager
+            .bound(&self.name, |bound| bound.strokes().len() > 1)
+    }
+
+    /// Set how long the layer stays active once it is activated.
+    pub fn set_mode(&self, mode: LayerMode) {
+        self.manager.set_mode(&self.name, mode);
+    }
+
+    /// How long the layer stays active once it is activated.
+    pub fn mode(&self) -> LayerMode {
+        self.manager.mode(&self.name)
+    }
+
+    /// Make this layer the active layer, see [`HotkeyManager::switch_layer`].
+    ///
+    /// [`HotkeyManager::switch_layer`]: crate::hotkey::manager::HotkeyManager::switch_layer
+    pub fn activate(&self) {
+        self.manager.switch_layer(&self.name);
+    }
+
+    /// Check if this layer is the active layer.
+    pub fn is_active(&self) -> bool {
+        self.manager.active_layer() == self.name
+    }
+}
@@ -0,0 +1,519 @@
+//! Register global hotkeys with callbacks.
+//!
+//! A [`HotkeyManager`] registers a single hook that watches the key and mouse button presses, and calls
+//! the callback of a [`Hotkey`] when its key or button is pressed while its modifiers are held,
+//! see [`Hotkey::matches`]. The held modifiers are taken from the [`KeyboardState`] of the session,
+//! so both sides of the keyboard are told apart, regardless of the mask reported by the operating system.
+//!
+//! Every activation fires once, the repeated press events sent while the key is held down are ignored,
+//! see [`EventMetaData::is_repeat`]. Hotkeys that overlap an already registered hotkey are rejected,
+//! see [`Hotkey::overlaps`].
+//!
+//! The manager also handles hotkeys made of several strokes, see the [`sequence`] module,
+//! and groups hotkeys into layers of which only one is active at a time, see the [`layer`] module.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hotkey::manager::HotkeyManager;
+//! use uiohook_rs::{hook_start, HookEvent};
+//!
+//! static OPENED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! let manager = HotkeyManager::new();
+//! manager
+//!     .register("Ctrl+K".parse().unwrap(), |_| {
+//!         OPENED.fetch_add(1, Ordering::SeqCst);
+//!     })
+//!     .unwrap();
+//!
+//! // `Ctrl+K` is already registered, and would be activated by the left control key as well.
+//! assert!(manager
+//!     .register("LeftCtrl+K".parse().unwrap(), |_| ())
+//!     .is_err());
+//!
+//! let handle = hook_start().expect("oops hook already running");
+//! for control in [Key::LeftControl, Key::RightControl] {
+//!     HookEvent::keyboard(control).press().post().unwrap();
+//!     // the key is held long enough to repeat.
+//!     for _ in 0..3 {
+//!         HookEvent::keyboard(Key::K).press().post().unwrap();
+//!     }
+//!     HookEvent::keyboard(Key::K).release().post().unwrap();
+//!     HookEvent::keyboard(control).release().post().unwrap();
+//! }
+//! handle.stop().unwrap();
+//!
+//! assert_eq!(OPENED.load(Ordering::SeqCst), 2);
+//! ```
+//!
+//! [`KeyboardState`]: crate::hook::state::KeyboardState
+//! [`EventMetaData::is_repeat`]: crate::hook::event::EventMetaData::is_repeat
+//! [`sequence`]: crate::hotkey::sequence
+//! [`layer`]: crate::hotkey::layer
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::hook::event::{now, EventKind, HookEvent, Modifiers};
+use crate::hook::session::HookSession;
+use crate::hook::state::KeyboardState;
+use crate::hook::Hook;
+use crate::hotkey::layer::{Layer, LayerMode, DEFAULT_LAYER};
+use crate::hotkey::sequence::{CancelReason, Sequence, SequenceEvent};
+use crate::hotkey::HotkeyConflict;
+use crate::hotkey::{Hotkey, Trigger};
+
+type HotkeyCallback = Arc<dyn Fn(&HookEvent) + Sync + Send>;
+type SequenceListener = Arc<dyn Fn(&SequenceEvent) + Sync + Send>;
+type LayerListener = Arc<dyn Fn(&str) + Sync + Send>;
+
+/// What a binding does once its sequence is completed.
+pub(crate) enum Command {
+    Call(HotkeyCallback),
+    Switch(String),
+}
+
+impl Command {
+    pub(crate) fn call<F: Fn(&HookEvent) + Sync + Send + 'static>(callback: F) -> Self {
+        Command::Call(Arc::new(callback))
+    }
+}
+
+struct Binding {
+    layer: String,
+    sequence: Sequence,
+    command: Command,
+    // The number of strokes pressed so far, and the time the last one was pressed.
+    progress: usize,
+    last_stroke: u128,
+}
+
+enum Advance {
+    Mismatch,
+    Progress,
+    Completed,
+}
+
+impl Binding {
+    fn is_sequence(&self) -> bool {
+        self.sequence.strokes().len() > 1
+    }
+
+    fn cancel(&mut self, reason: CancelReason, actions: &mut Vec<Action>) {
+        self.progress = 0;
+        actions.push(Action::Notify(SequenceEvent::Cancelled {
+            sequence: self.sequence.clone(),
+            reason,
+        }));
+    }
+
+    /// Advance the binding if `trigger` along with `modifiers` matches its next stroke.
+    fn advance(
+        &mut self,
+        trigger: Trigger,
+        modifiers: Modifiers,
+        time: u128,
+        actions: &mut Vec<Action>,
+    ) -> Advance {
+        let next = &self.sequence.strokes()[self.progress];
+        if next.trigger() != trigger || !next.matches(modifiers) {
+            return Advance::Mismatch;
+        }
+
+        self.progress += 1;
+        self.last_stroke = time;
+        if self.progress < self.sequence.strokes().len() {
+            actions.push(Action::Notify(SequenceEvent::Progress {
+                sequence: self.sequence.clone(),
+                matched: self.progress,
+            }));
+            return Advance::Progress;
+        }
+
+        self.progress = 0;
+        if self.is_sequence() {
+            actions.push(Action::Notify(SequenceEvent::Completed {
+                sequence: self.sequence.clone(),
+            }));
+        }
+        Advance::Completed
+    }
+}
+
+enum Action {
+    Call(HotkeyCallback),
+    Notify(SequenceEvent),
+    Switched(String),
+}
+
+#[derive(Clone, Default)]
+struct Listeners {
+    sequence: Vec<SequenceListener>,
+    layer: Vec<LayerListener>,
+}
+
+impl Listeners {
+    /// Perform the actions triggered by `event`, or by a call to the manager if there is no event.
+    fn perform(&self, actions: Vec<Action>, event: Option<&HookEvent>) {
+        for action in actions {
+            match action {
+                Action::Call(callback) => {
+                    if let Some(event) = event {
+                        callback(event);
+                    }
+                }
+                Action::Notify(notification) => {
+                    for listener in &self.sequence {
+                        listener(&notification);
+                    }
+                }
+                Action::Switched(layer) => {
+                    for listener in &self.layer {
+                        listener(&layer);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Registry {
+    bindings: Vec<Binding>,
+    listeners: Listeners,
+    modes: HashMap<String, LayerMode>,
+    active: String,
+    // The last persistent layer, which transient layers return to.
+    base: String,
+    // The time the active layer was entered.
+    entered_at: u128,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            bindings: Vec::new(),
+            listeners: Listeners::default(),
+            modes: HashMap::new(),
+            active: DEFAULT_LAYER.to_string(),
+            base: DEFAULT_LAYER.to_string(),
+            entered_at: 0,
+        }
+    }
+}
+
+impl Registry {
+    fn mode(&self, layer: &str) -> LayerMode {
+        self.modes.get(layer).copied().unwrap_or_default()
+    }
+
+    fn switch(&mut self, layer: &str, time: u128, actions: &mut Vec<Action>) {
+        if self.active == layer {
+            return;
+        }
+
+        for binding in self.bindings.iter_mut().filter(|b| b.progress > 0) {
+            binding.cancel(CancelReason::Interrupted, actions);
+        }
+        self.active = layer.to_string();
+        if self.mode(layer) == LayerMode::Persistent {
+            self.base = layer.to_string();
+        }
+        self.entered_at = time;
+        actions.push(Action::Switched(layer.to_string()));
+    }
+
+    fn leave_transient(&mut self, time: u128, actions: &mut Vec<Action>) {
+        if self.mode(&self.active) != LayerMode::Persistent {
+            let base = self.base.clone();
+            self.switch(&base, time, actions);
+        }
+    }
+
+    /// The callbacks and notifications triggered by the event.
+    fn handle(&mut self, event: &HookEvent) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let time = match event.metadata.time {
+            0 => now(),
+            time => time,
+        };
+        let trigger = match &event.kind {
+            // The repeated press events of a held key are told apart by the session, see `EventMode::REPEAT`.
+            EventKind::KeyPressed(_) if event.is_repeat() => return actions,
+            EventKind::KeyPressed(data) => Trigger::Key(data.keycode),
+            EventKind::MousePressed(data) => Trigger::Button(data.button),
+            EventKind::Enabled | EventKind::Disabled => {
+                for binding in self.bindings.iter_mut().filter(|b| b.progress > 0) {
+                    binding.cancel(CancelReason::Interrupted, &mut actions);
+                }
+                self.leave_transient(time, &mut actions);
+                return actions;
+            }
+            _ => return actions,
+        };
+
+        let mut modifiers = match KeyboardState::current() {
+            Some(keyboard) => keyboard.modifiers().keys(),
+            None => event.metadata.mask.keys(),
+        };
+        // A modifier key can be the trigger of a hotkey, in which case it is not one of its modifiers,
+        // otherwise it is only pressed on the way to the next stroke and does not cancel sequences.
+        let mut is_modifier = false;
+        if let Trigger::Key(key) = trigger {
+            let modifier = Modifiers::from_key(key).keys();
+            is_modifier = !modifier.is_empty();
+            modifiers -= modifier;
+        }
+
+        if let LayerMode::Transient {
+            timeout: Some(timeout),
+        } = self.mode(&self.active)
+        {
+            if time.saturating_sub(self.entered_at) > timeout.as_millis() {
+                self.leave_transient(time, &mut actions);
+            }
+        }
+
+        let active = self.active.clone();
+        let (mut advanced, mut completed, mut switch) = (false, false, None);
+        for binding in self.bindings.iter_mut().filter(|b| b.layer == active) {
+            if binding.progress > 0
+                && time.saturating_sub(binding.last_stroke) > binding.sequence.timeout().as_millis()
+            {
+                binding.cancel(CancelReason::TimedOut, &mut actions);
+            }
+
+            let mut advance = binding.advance(trigger, modifiers, time, &mut actions);
+            if matches!(advance, Advance::Mismatch) && binding.progress > 0 && !is_modifier {
+                binding.cancel(CancelReason::UnrelatedInput, &mut actions);
+                // The press might start the sequence over.
+                advance = binding.advance(trigger, modifiers, time, &mut actions);
+            }
+
+            match advance {
+                Advance::Mismatch => (),
+                Advance::Progress => advanced = true,
+                Advance::Completed => {
+                    advanced = true;
+                    completed = true;
+                    match &binding.command {
+                        Command::Call(callback) => actions.push(Action::Call(callback.clone())),
+                        Command::Switch(layer) => switch = Some(layer.clone()),
+                    }
+                }
+            }
+        }
+
+        // Transient layers are left once one of their hotkeys is activated, or when anything else is pressed.
+        if let Some(layer) = switch {
+            self.switch(&layer, time, &mut actions);
+        } else if completed || (!advanced && !is_modifier) {
+            self.leave_transient(time, &mut actions);
+        }
+
+        actions
+    }
+}
+
+/// Calls the callbacks of registered hotkeys, see the [module level documentation](crate::hotkey::manager).
+///
+/// The hook of the manager is unregistered when the manager is dropped.
+pub struct HotkeyManager {
+    registry: Arc<Mutex<Registry>>,
+    // Kept to unregister the hook on drop.
+    _hook: Hook,
+}
+
+impl Default for HotkeyManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HotkeyManager {
+    /// Create a manager for the default session, see [`HookSession::global`].
+    pub fn new() -> Self {
+        Self::for_session(HookSession::global())
+    }
+
+    /// Create a manager for the specified session.
+    pub fn for_session(session: &HookSession) -> Self {
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let hook_registry = registry.clone();
+        let mut hook = Hook::new(move |event| {
+            // The callbacks are called after the registry is unlocked,
+            // so they are free to register and unregister hotkeys.
+            let (actions, listeners) = {
+                let mut registry = hook_registry.lock();
+                (registry.handle(event), registry.listeners.clone())
+            };
+            listeners.perform(actions, Some(event));
+        });
+        hook.register_in(session);
+
+        HotkeyManager {
+            registry,
+            _hook: hook,
+        }
+    }
+
+    /// Register a hotkey in the default layer, the callback is called with the press event that activated
+    /// the hotkey.
+    ///
+    /// Returns an error if the hotkey overlaps a registered hotkey or the first stroke of a registered
+    /// sequence, in which case it is not registered.
+    pub fn register<F>(&self, hotkey: Hotkey, callback: F) -> Result<(), HotkeyConflict>
+    where
+        F: Fn(&HookEvent) + Sync + Send + 'static,
+    {
+        self.layer(DEFAULT_LAYER).register(hotkey, callback)
+    }
+
+    /// Register a sequence of hotkeys in the default layer, the callback is called with the press event
+    /// of the last stroke.
+    ///
+    /// Returns an error if the sequence overlaps a registered sequence or hotkey, see [`Sequence::overlaps`],
+    /// in which case it is not registered.
+    pub fn register_sequence<F>(
+        &self,
+        sequence: Sequence,
+        callback: F,
+    ) -> Result<(), HotkeyConflict>
+    where
+        F: Fn(&HookEvent) + Sync + Send + 'static,
+    {
+        self.layer(DEFAULT_LAYER)
+            .register_sequence(sequence, callback)
+    }
+
+    /// Unregister a hotkey from the default layer, returns `false` if the hotkey was not registered.
+    pub fn unregister(&self, hotkey: &Hotkey) -> bool {
+        self.layer(DEFAULT_LAYER).unregister(hotkey)
+    }
+
+    /// Unregister a sequence from the default layer, returns `false` if the sequence was not registered.
+    pub fn unregister_sequence(&self, sequence: &Sequence) -> bool {
+        self.layer(DEFAULT_LAYER).unregister_sequence(sequence)
+    }
+
+    /// Add a listener that is notified whenever a sequence of more than one stroke advances,
+    /// completes or is cancelled, see [`SequenceEvent`].
+    ///
+    /// The listeners are called on the thread the hooks of the session are called on.
+    pub fn on_sequence<F: Fn(&SequenceEvent) + Sync + Send + 'static>(&self, listener: F) {
+        self.registry
+            .lock()
+            .listeners
+            .sequence
+            .push(Arc::new(listener));
+    }
+
+    /// The registered hotkeys and sequences of the default layer that overlap `sequence`.
+    pub fn conflicts(&self, sequence: &Sequence) -> Vec<Sequence> {
+        self.layer(DEFAULT_LAYER).conflicts(sequence)
+    }
+
+    /// The hotkeys registered in the default layer, in the order they were registered.
+    pub fn hotkeys(&self) -> Vec<Hotkey> {
+        self.layer(DEFAULT_LAYER).hotkeys()
+    }
+
+    /// The sequences of more than one stroke registered in the default layer, in the order they were registered.
+    pub fn sequences(&self) -> Vec<Sequence> {
+        self.layer(DEFAULT_LAYER).sequences()
+    }
+
+    /// Get the layer named `name`, layers do not need to be created before they are used.
+    pub fn layer(&self, name: &str) -> Layer<'_> {
+        Layer::new(self, name)
+    }
+
+    /// The name of the active layer.
+    pub fn active_layer(&self) -> String {
+        self.registry.lock().active.clone()
+    }
+
+    /// Make `layer` the active layer, sequences in progress are cancelled.
+    pub fn switch_layer(&self, layer: &str) {
+        let mut actions = Vec::new();
+        let listeners = {
+            let mut registry = self.registry.lock();
+            registry.switch(layer, now(), &mut actions);
+            registry.listeners.clone()
+        };
+        listeners.perform(actions, None);
+    }
+
+    /// Add a listener that is called with the name of the new active layer whenever the active layer changes.
+    ///
+    /// The listeners are called on the thread the hooks of the session are called on,
+    /// or on the thread that called [`switch_layer`](HotkeyManager::switch_layer).
+    pub fn on_layer_change<F: Fn(&str) + Sync + Send + 'static>(&self, listener: F) {
+        self.registry
+            .lock()
+            .listeners
+            .layer
+            .push(Arc::new(listener));
+    }
+
+    pub(crate) fn bind(
+        &self,
+        layer: &str,
+        sequence: Sequence,
+        command: Command,
+    ) -> Result<(), HotkeyConflict> {
+        let mut registry = self.registry.lock();
+        if let Some(binding) = registry
+            .bindings
+            .iter()
+            .find(|binding| binding.layer == layer && binding.sequence.overlaps(&sequence))
+        {
+            return Err(HotkeyConflict {
+                existing: binding.sequence.clone(),
+                sequence,
+            });
+        }
+
+        registry.bindings.push(Binding {
+            layer: layer.to_string(),
+            sequence,
+            command,
+            progress: 0,
+            last_stroke: 0,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn unbind(&self, layer: &str, sequence: &Sequence) -> bool {
+        let mut registry = self.registry.lock();
+        let count = registry.bindings.len();
+        registry.bindings.retain(|binding| {
+            binding.layer != layer || binding.sequence.strokes() != sequence.strokes()
+        });
+        registry.bindings.len() != count
+    }
+
+    /// The sequences bound in `layer` that pass the filter, in the order they were registered.
+    pub(crate) fn bound<F: Fn(&Sequence) -> bool>(&self, layer: &str, filter: F) -> Vec<Sequence> {
+        self.registry
+            .lock()
+            .bindings
+            .iter()
+            .filter(|binding| binding.layer == layer && filter(&binding.sequence))
+            .map(|binding| binding.sequence.clone())
+            .collect()
+    }
+
+    pub(crate) fn set_mode(&self, layer: &str, mode: LayerMode) {
+        self.registry.lock().modes.insert(layer.to_string(), mode);
+    }
+
+    pub(crate) fn mode(&self, layer: &str) -> LayerMode {
+        self.registry.lock().mode(layer)
+    }
+}
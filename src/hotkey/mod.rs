@@ -0,0 +1,378 @@
+//! Keyboard shortcuts made of modifiers and a single key or mouse button.
+//!
+//! A [`Hotkey`] is usually parsed from an accelerator string, where the modifiers and the key are joined by
+//! `+`, like `"Ctrl+Shift+K"`, `"Alt+F4"` or `"Meta+Button4"`. Parsing is case insensitive and ignores
+//! whitespace around the parts of the string. The key is the last part, every other part must be a modifier:
+//!
+//! | Modifier | Aliases                                         |
+//! |----------|-------------------------------------------------|
+//! | `Ctrl`   | `Control`                                       |
+//! | `Shift`  |                                                 |
+//! | `Alt`    | `Option`, `Opt`                                 |
+//! | `Meta`   | `Cmd`, `Command`, `Win`, `Windows`, `Super`     |
+//!
+//! A modifier matches either side of the keyboard, unless it is prefixed with `Left` or `Right`
+//! (or just `L` and `R`), as in `"LeftCtrl+C"` or `"RAlt+E"`. `AltGr` is an alias of `RightAlt`.
+//!
+//! The key is the name of a [`Key`] variant like `K`, `F4` or `NumPadEnter`, or one of the common aliases:
+//! digits and punctuation characters stand for the key that types them, `Return` stands for `Enter`,
+//! `Esc` for `Escape`, `Del` for `Delete`, `PgUp` and `PgDn` for the page keys, and so on.
+//! The mouse buttons are named `Button1` through `Button5`, with `MouseLeft`, `MouseRight` and `MouseMiddle`
+//! as aliases of the first three.
+//!
+//! A hotkey is displayed in a canonical form that parses back into the same hotkey, the modifiers
+//! are ordered `Ctrl`, `Shift`, `Alt`, `Meta` and the key is named after its [`Key`] variant.
+//!
+//! # Example
+//! ```rust
+//! use uiohook_rs::hook::event::{Key, Modifiers, MouseButton};
+//! use uiohook_rs::hotkey::{Hotkey, ParseHotkeyError, Trigger};
+//!
+//! let save: Hotkey = "cmd + shift + s".parse().unwrap();
+//! assert_eq!(save.modifiers(), Modifiers::META | Modifiers::SHIFT);
+//! assert_eq!(save.trigger(), Trigger::Key(Key::S));
+//! assert_eq!(save.to_string(), "Shift+Meta+S");
+//!
+//! let back: Hotkey = "Meta+Button4".parse().unwrap();
+//! assert_eq!(back, Hotkey::button(Modifiers::META, MouseButton::Extra1));
+//!
+//! let submit: Hotkey = "LeftCtrl+Return".parse().unwrap();
+//! assert_eq!(submit, Hotkey::new(Modifiers::LEFT_CONTROL, Key::Enter));
+//! assert_eq!(submit.to_string().parse::<Hotkey>().unwrap(), submit);
+//!
+//! assert_eq!(
+//!     "Ctrl+Shift".parse::<Hotkey>(),
+//!     Err(ParseHotkeyError::MissingKey)
+//! );
+//! assert_eq!(
+//!     "Ctrl+Kk".parse::<Hotkey>(),
+//!     Err(ParseHotkeyError::UnknownName("Kk".to_string()))
+//! );
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+pub use self::error::{HotkeyConflict, ParseHotkeyError};
+use crate::hook::event::{Key, Modifiers, MouseButton};
+
+mod error;
+pub mod layer;
+pub mod manager;
+pub mod sequence;
+
+/// The key or mouse button that activates a [`Hotkey`] once the modifiers are held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Key(Key),
+    Button(MouseButton),
+}
+
+impl From<Key> for Trigger {
+    fn from(key: Key) -> Self {
+        Trigger::Key(key)
+    }
+}
+
+impl From<MouseButton> for Trigger {
+    fn from(button: MouseButton) -> Self {
+        Trigger::Button(button)
+    }
+}
+
+/// A set of modifiers along with a key or a mouse button, see the [module level documentation](crate::hotkey).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    modifiers: Modifiers,
+    trigger: Trigger,
+}
+
+impl Hotkey {
+    /// Create a hotkey activated by `key`, only the modifier keys of `modifiers` are kept.
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        Hotkey {
+            modifiers: modifiers.keys(),
+            trigger: Trigger::Key(key),
+        }
+    }
+
+    /// Create a hotkey activated by a mouse button, only the modifier keys of `modifiers` are kept.
+    pub fn button(modifiers: Modifiers, button: MouseButton) -> Self {
+        Hotkey {
+            modifiers: modifiers.keys(),
+            trigger: Trigger::Button(button),
+        }
+    }
+
+    /// The modifiers that must be held.
+    ///
+    /// For every modifier, both the left and right flags are set if either side of the keyboard
+    /// matches, otherwise only the flag of the required side is set.
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// The key or mouse button that activates the hotkey.
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    /// Check if the held modifier keys satisfy the modifiers of the hotkey.
+    ///
+    /// Modifiers that match either side of the keyboard are satisfied by holding the left key, the right key
+    /// or both, while modifiers of a specific side are only satisfied by holding that side alone.
+    /// Modifiers that are not part of the hotkey must not be held.
+    pub fn matches(&self, held: Modifiers) -> bool {
+        MODIFIERS.iter().all(|(group, left, right, _)| {
+            let required = self.modifiers & *group;
+            let held = held & *group;
+            if required == *group {
+                !held.is_empty()
+            } else if required == *left || required == *right {
+                held == required
+            } else {
+                held.is_empty()
+            }
+        })
+    }
+
+    /// Check if both hotkeys are activated by the same key or button along with the same held modifiers,
+    /// for example `Ctrl+K` overlaps `LeftCtrl+K` since both match when the left control key is held.
+    pub fn overlaps(&self, other: &Hotkey) -> bool {
+        self.trigger == other.trigger
+            && MODIFIERS.iter().all(|(group, _, _, _)| {
+                let (this, other) = (self.modifiers & *group, other.modifiers & *group);
+                this.intersects(other) || (this.is_empty() && other.is_empty())
+            })
+    }
+}
+
+impl From<Key> for Hotkey {
+    fn from(key: Key) -> Self {
+        Hotkey::new(Modifiers::empty(), key)
+    }
+}
+
+impl From<MouseButton> for Hotkey {
+    fn from(button: MouseButton) -> Self {
+        Hotkey::button(Modifiers::empty(), button)
+    }
+}
+
+// The modifier groups in their display order, along with the flags of their left and right keys.
+const MODIFIERS: [(Modifiers, Modifiers, Modifiers, &str); 4] = [
+    (
+        Modifiers::CONTROL,
+        Modifiers::LEFT_CONTROL,
+        Modifiers::RIGHT_CONTROL,
+        "Ctrl",
+    ),
+    (
+        Modifiers::SHIFT,
+        Modifiers::LEFT_SHIFT,
+        Modifiers::RIGHT_SHIFT,
+        "Shift",
+    ),
+    (
+        Modifiers::ALT,
+        Modifiers::LEFT_ALT,
+        Modifiers::RIGHT_ALT,
+        "Alt",
+    ),
+    (
+        Modifiers::META,
+        Modifiers::LEFT_META,
+        Modifiers::RIGHT_META,
+        "Meta",
+    ),
+];
+
+const BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "Button1"),
+    (MouseButton::Right, "Button2"),
+    (MouseButton::Middle, "Button3"),
+    (MouseButton::Extra1, "Button4"),
+    (MouseButton::Extra2, "Button5"),
+];
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (group, left, right, name) in MODIFIERS {
+            let held = self.modifiers & group;
+            if held == group {
+                write!(f, "{}+", name)?;
+            } else if held == left {
+                write!(f, "Left{}+", name)?;
+            } else if held == right {
+                write!(f, "Right{}+", name)?;
+            }
+        }
+
+        match self.trigger {
+            Trigger::Key(key) => write!(f, "{}", key),
+            Trigger::Button(button) => match BUTTONS.iter().find(|(b, _)| *b == button) {
+                Some((_, name)) => f.write_str(name),
+                None => write!(f, "{}", button),
+            },
+        }
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = ParseHotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ParseHotkeyError::Empty);
+        }
+
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let last = parts.pop().unwrap_or_default();
+
+        let mut modifiers = Modifiers::empty();
+        for part in parts {
+            if part.is_empty() {
+                return Err(ParseHotkeyError::Empty);
+            }
+            let modifier = match parse_modifier(part) {
+                Some(modifier) => modifier,
+                None if parse_trigger(part).is_some() => {
+                    return Err(ParseHotkeyError::NotAModifier(part.to_string()))
+                }
+                None => return Err(ParseHotkeyError::UnknownName(part.to_string())),
+            };
+            let group = MODIFIERS
+                .iter()
+                .map(|(group, _, _, _)| *group)
+                .find(|group| group.intersects(modifier))
+                .unwrap_or(modifier);
+            if modifiers.intersects(group) {
+                return Err(ParseHotkeyError::DuplicateModifier(part.to_string()));
+            }
+            modifiers |= modifier;
+        }
+
+        match parse_trigger(last) {
+            Some(trigger) => Ok(Hotkey { modifiers, trigger }),
+            None if last.is_empty() || parse_modifier(last).is_some() => {
+                Err(ParseHotkeyError::MissingKey)
+            }
+            None => Err(ParseHotkeyError::UnknownName(last.to_string())),
+        }
+    }
+}
+
+fn parse_modifier(name: &str) -> Option<Modifiers> {
+    let name = name.to_ascii_lowercase();
+    if name == "altgr" {
+        return Some(Modifiers::RIGHT_ALT);
+    }
+
+    let sided = |prefixes: [&str; 2]| {
+        prefixes
+            .iter()
+            .filter_map(|prefix| name.strip_prefix(prefix))
+            .find_map(modifier_group)
+    };
+    if let Some((group, _, _)) = modifier_group(&name) {
+        Some(group)
+    } else if let Some((_, left, _)) = sided(["left", "l"]) {
+        Some(left)
+    } else {
+        sided(["right", "r"]).map(|(_, _, right)| right)
+    }
+}
+
+/// The modifier group named `name`, along with the flags of its left and right keys.
+fn modifier_group(name: &str) -> Option<(Modifiers, Modifiers, Modifiers)> {
+    let group = match name {
+        "ctrl" | "control" => 0,
+        "shift" => 1,
+        "alt" | "option" | "opt" => 2,
+        "meta" | "cmd" | "command" | "win" | "windows" | "super" => 3,
+        _ => return None,
+    };
+    let (group, left, right, _) = MODIFIERS[group];
+    Some((group, left, right))
+}
+
+fn parse_trigger(name: &str) -> Option<Trigger> {
+    let lower = name.to_ascii_lowercase();
+    if let Some(button) = parse_button(&lower) {
+        return Some(Trigger::Button(button));
+    }
+    if let Some(key) = key_alias(&lower) {
+        return Some(Trigger::Key(key));
+    }
+
+    Key::iter()
+        .filter(|key| !matches!(key, Key::Unknown(_) | Key::Undefined))
+        .find(|key| key.to_string().eq_ignore_ascii_case(name))
+        .map(Trigger::Key)
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    let button = match name {
+        "mouseleft" => MouseButton::Left,
+        "mouseright" => MouseButton::Right,
+        "mousemiddle" => MouseButton::Middle,
+        _ => {
+            let number = name
+                .strip_prefix("button")
+                .or_else(|| name.strip_prefix("mouse"))?;
+            match number {
+                "1" => MouseButton::Left,
+                "2" => MouseButton::Right,
+                "3" => MouseButton::Middle,
+                "4" => MouseButton::Extra1,
+                "5" => MouseButton::Extra2,
+                _ => return None,
+            }
+        }
+    };
+    Some(button)
+}
+
+fn key_alias(name: &str) -> Option<Key> {
+    let key = match name {
+        "0" => Key::Key0,
+        "1" => Key::Key1,
+        "2" => Key::Key2,
+        "3" => Key::Key3,
+        "4" => Key::Key4,
+        "5" => Key::Key5,
+        "6" => Key::Key6,
+        "7" => Key::Key7,
+        "8" => Key::Key8,
+        "9" => Key::Key9,
+        "-" => Key::Minus,
+        "=" | "equal" => Key::Equals,
+        "," => Key::Comma,
+        "." => Key::Period,
+        "/" => Key::Slash,
+        "\\" => Key::BackSlash,
+        ";" => Key::SemiColon,
+        "'" => Key::Quote,
+        "[" => Key::OpenBracket,
+        "]" => Key::CloseBracket,
+        "`" | "backtick" | "grave" => Key::Backquote,
+        "return" => Key::Enter,
+        "esc" => Key::Escape,
+        "del" => Key::Delete,
+        "ins" => Key::Insert,
+        "pgup" => Key::PageUp,
+        "pgdn" | "pgdown" => Key::PageDown,
+        "arrowup" => Key::Up,
+        "arrowdown" => Key::Down,
+        "arrowleft" => Key::Left,
+        "arrowright" => Key::Right,
+        "spacebar" => Key::Space,
+        "caps" => Key::CapsLock,
+        "menu" | "apps" => Key::ContextMenu,
+        "prtsc" | "printscr" => Key::PrintScreen,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,209 @@
+//! Hotkeys made of several strokes, like `Ctrl+K, Ctrl+C`.
+//!
+//! A [`Sequence`] is activated by pressing its strokes one after the other, each stroke is a [`Hotkey`].
+//! Sequences are parsed from the accelerators of their strokes separated by commas, and are registered with
+//! [`HotkeyManager::register_sequence`], which tracks the progress of every registered sequence:
+//!
+//! *   A stroke that matches the next stroke of a sequence advances it, the callback of the sequence
+//!     is called once its last stroke is pressed.
+//! *   Pressing a key or a mouse button that does not match the next stroke cancels the sequence, except for
+//!     modifier keys which are needed to press the next stroke.
+//! *   A sequence is cancelled if the next stroke is not pressed within its timeout. Timeouts are checked
+//!     as the events arrive, so a sequence that timed out is only reported as cancelled on the next press.
+//!
+//! Listeners registered with [`HotkeyManager::on_sequence`] are notified with a [`SequenceEvent`] whenever a
+//! sequence advances, completes or is cancelled, which is useful to show the user what the manager is waiting for.
+//!
+//! # Example
+//! ```rust
+//! # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::{Arc, Mutex};
+//!
+//! use uiohook_rs::hook::event::{Key, Modifiers};
+//! use uiohook_rs::hotkey::manager::HotkeyManager;
+//! use uiohook_rs::hotkey::sequence::SequenceEvent;
+//! use uiohook_rs::{hook_start, HookEvent};
+//!
+//! static COMMENTED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! let status = Arc::new(Mutex::new(Vec::new()));
+//! let listener_status = status.clone();
+//! let manager = HotkeyManager::new();
+//! manager.on_sequence(move |event| {
+//!     let mut status = listener_status.lock().unwrap();
+//!     match event {
+//!         SequenceEvent::Progress { sequence, matched } => {
+//!             let pressed = sequence.strokes()[matched - 1];
+//!             status.push(format!("{} was pressed, waiting for the next key", pressed))
+//!         }
+//!         SequenceEvent::Completed { .. } => status.push("done".to_string()),
+//!         SequenceEvent::Cancelled { reason, .. } => status.push(format!("{:?}", reason)),
+//!     }
+//! });
+//! manager
+//!     .register_sequence("Ctrl+K, Ctrl+C".parse().unwrap(), |_| {
+//!         COMMENTED.fetch_add(1, Ordering::SeqCst);
+//!     })
+//!     .unwrap();
+//!
+//! let stroke = |key| {
+//!     HookEvent::keyboard(key)
+//!         .with_mask(Modifiers::LEFT_CONTROL)
+//!         .pair()
+//!         .post()
+//!         .unwrap()
+//! };
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::LeftControl).press().post().unwrap();
+//! stroke(Key::K);
+//! stroke(Key::X);
+//! stroke(Key::K);
+//! stroke(Key::C);
+//! HookEvent::keyboard(Key::LeftControl).release().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! assert_eq!(COMMENTED.load(Ordering::SeqCst), 1);
+//! assert_eq!(
+//!     *status.lock().unwrap(),
+//!     vec![
+//!         "Ctrl+K was pressed, waiting for the next key",
+//!         "UnrelatedInput",
+//!         "Ctrl+K was pressed, waiting for the next key",
+//!         "done",
+//!     ]
+//! );
+//! ```
+//!
+//! [`HotkeyManager::register_sequence`]: crate::hotkey::manager::HotkeyManager::register_sequence
+//! [`HotkeyManager::on_sequence`]: crate::hotkey::manager::HotkeyManager::on_sequence
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::hotkey::Hotkey;
+use crate::hotkey::ParseHotkeyError;
+
+/// Hotkeys that must be pressed one after the other, see the [module level documentation](crate::hotkey::sequence).
+///
+/// The default timeout between strokes is 2 seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sequence {
+    strokes: Vec<Hotkey>,
+    timeout: Duration,
+}
+
+impl Sequence {
+    /// Create a sequence from its strokes, returns `None` if there are no strokes.
+    pub fn new<I: IntoIterator<Item = Hotkey>>(strokes: I) -> Option<Self> {
+        let strokes: Vec<Hotkey> = strokes.into_iter().collect();
+        if strokes.is_empty() {
+            return None;
+        }
+
+        Some(Sequence {
+            strokes,
+            timeout: Duration::from_secs(2),
+        })
+    }
+
+    /// Set how long to wait for each stroke after the previous one.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The strokes of the sequence, there is always at least one.
+    pub fn strokes(&self) -> &[Hotkey] {
+        &self.strokes
+    }
+
+    /// How long to wait for each stroke after the previous one.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Check if one of the sequences starts with the other, stroke by stroke, see [`Hotkey::overlaps`].
+    ///
+    /// Pressing the strokes of the shorter sequence would activate it before the longer one
+    /// can be completed, so overlapping sequences cannot be registered together.
+    pub fn overlaps(&self, other: &Sequence) -> bool {
+        self.strokes
+            .iter()
+            .zip(&other.strokes)
+            .all(|(this, other)| this.overlaps(other))
+    }
+}
+
+impl From<Hotkey> for Sequence {
+    fn from(hotkey: Hotkey) -> Self {
+        Sequence {
+            strokes: vec![hotkey],
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, stroke) in self.strokes.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", stroke)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = ParseHotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A comma right after a `+` is the comma key rather than the separator, as in `Ctrl+,`.
+        let mut strokes: Vec<String> = Vec::new();
+        for part in s.split(',') {
+            match strokes.last_mut() {
+                Some(stroke) if stroke.trim_end().ends_with('+') => {
+                    stroke.push(',');
+                    stroke.push_str(part);
+                }
+                _ => strokes.push(part.to_string()),
+            }
+        }
+
+        let strokes = strokes
+            .iter()
+            .map(|stroke| stroke.parse())
+            .collect::<Result<Vec<Hotkey>, _>>()?;
+        Sequence::new(strokes).ok_or(ParseHotkeyError::Empty)
+    }
+}
+
+/// The reason a sequence was cancelled, see [`SequenceEvent::Cancelled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The next stroke was not pressed within the timeout of the sequence.
+    TimedOut,
+    /// A key or a mouse button that does not match the next stroke was pressed.
+    UnrelatedInput,
+    /// The session was stopped or restarted.
+    Interrupted,
+}
+
+/// A change in the progress of a sequence of more than one stroke, see [`HotkeyManager::on_sequence`].
+///
+/// [`HotkeyManager::on_sequence`]: crate::hotkey::manager::HotkeyManager::on_sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// The first `matched` strokes of the sequence were pressed, and the next one is expected.
+    Progress { sequence: Sequence, matched: usize },
+    /// The last stroke of the sequence was pressed, and its callback is about to be called.
+    Completed { sequence: Sequence },
+    /// The sequence was cancelled before it was completed.
+    Cancelled {
+        sequence: Sequence,
+        reason: CancelReason,
+    },
+}
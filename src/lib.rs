@@ -3,8 +3,15 @@
 mod error;
 pub use error::*;
 
+pub mod gesture;
 pub mod hook;
+pub mod hotkey;
+#[cfg_attr(rustdoc, doc(cfg(feature = "recording")))]
+#[cfg(feature = "recording")]
+pub mod recording;
 pub mod system_properties;
+pub mod testing;
+pub mod text;
 
 #[doc(inline)]
 pub use hook::event::{EventKind, EventMetaData, EventType, HookEvent};
@@ -13,6 +13,7 @@ macro_rules! map_native {
 
         $(#[$struct_doc])*
         #[derive(Debug, Clone, Default)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct $rusty {
             $(
                 $(#[$field_doc])*
@@ -0,0 +1,263 @@
+//! Record input sessions to files, and read them back.
+//!
+//! This module is only available with the `recording` feature. A recorded session starts with a [`Header`],
+//! describing the format version, the screens and the [`SystemProperties`] of the system it was recorded on,
+//! followed by the recorded events, each along with the time it was received since the recording started,
+//! see [`RecordedEvent`]. Sessions are recorded with a [`Recorder`], or written by hand with a
+//! [`SessionWriter`], are read with a [`SessionReader`], and are replayed with a [`Player`].
+//!
+//! A session is encoded in one of two ways, see [`Encoding`]:
+//!
+//! *   [`JsonLines`] - a JSON object per line, the header comes first and every following line is an event.
+//!     The events are encoded as described in the [`serialize`] module, so sessions can be read and even
+//!     edited by hand.
+//! *   [`Binary`] - a compact encoding made of the `UIOHREC` magic bytes, the format version as a little
+//!     endian `u32`, then the header and the events encoded with bincode one after the other.
+//!
+//! The encoding of a session is detected when it is read, so both can be read the same way.
+//!
+//! # Example
+//! ```rust
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::recording::{Encoding, Header, RecordedEvent, SessionReader, SessionWriter};
+//! use uiohook_rs::HookEvent;
+//!
+//! let header = Header::current();
+//! let mut writer = SessionWriter::new(Vec::new(), Encoding::JsonLines, &header).unwrap();
+//! let (press, release) = HookEvent::keyboard(Key::A).pair().into();
+//! writer.write(&RecordedEvent { offset: 0, event: press }).unwrap();
+//! writer.write(&RecordedEvent { offset: 80, event: release }).unwrap();
+//! let session = writer.into_inner();
+//!
+//! let reader = SessionReader::new(&session[..]).unwrap();
+//! assert_eq!(reader.encoding(), Encoding::JsonLines);
+//! let offsets: Vec<u64> = reader.map(|recorded| recorded.unwrap().offset).collect();
+//! assert_eq!(offsets, vec![0, 80]);
+//! ```
+//!
+//! [`SystemProperties`]: crate::system_properties::SystemProperties
+//! [`JsonLines`]: Encoding::JsonLines
+//! [`Binary`]: Encoding::Binary
+//! [`serialize`]: crate::hook::serialize
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub use self::player::{Geometry, Player, PlayerState};
+pub use self::recorder::{Recorder, RecorderState};
+use crate::error::RecordingError;
+use crate::hook::event::{now, HookEvent};
+use crate::system_properties::{self, ScreenData, SystemProperties};
+
+mod player;
+mod recorder;
+
+/// The version of the session format written by this library.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 7] = b"UIOHREC";
+
+/// How a session is encoded, see the [module level documentation](crate::recording).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// A human readable JSON object per line.
+    JsonLines,
+    /// A compact binary encoding.
+    Binary,
+}
+
+/// Describes the system a session was recorded on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    /// The version of the format, see [`FORMAT_VERSION`].
+    pub version: u32,
+    /// The time the recording started, as a unix timestamp in milliseconds.
+    pub started_at: u128,
+    /// The screens connected when the recording started, empty if they could not be queried.
+    pub screens: Vec<ScreenData>,
+    /// The system properties when the recording started.
+    pub properties: SystemProperties,
+}
+
+impl Header {
+    /// A header describing the current system, as of now.
+    pub fn current() -> Self {
+        Header {
+            version: FORMAT_VERSION,
+            started_at: now(),
+            screens: system_properties::screen_info().unwrap_or_default(),
+            properties: SystemProperties::current(),
+        }
+    }
+}
+
+/// An event of a recorded session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// The number of milliseconds between the start of the recording and the event,
+    /// not including the time the recording was paused.
+    pub offset: u64,
+    /// The event, as it was received by the hook.
+    pub event: HookEvent,
+}
+
+/// Writes a session, see the [module level documentation](crate::recording).
+///
+/// The writer is not buffered, wrap files in a [`BufWriter`](std::io::BufWriter).
+pub struct SessionWriter<W: Write> {
+    writer: W,
+    encoding: Encoding,
+}
+
+impl<W: Write> SessionWriter<W> {
+    /// Start a session by writing its header.
+    pub fn new(mut writer: W, encoding: Encoding, header: &Header) -> Result<Self, RecordingError> {
+        match encoding {
+            Encoding::JsonLines => {
+                serde_json::to_writer(&mut writer, header)?;
+                writer.write_all(b"\n")?;
+            }
+            Encoding::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&header.version.to_le_bytes())?;
+                bincode::serialize_into(&mut writer, header)?;
+            }
+        }
+
+        Ok(SessionWriter { writer, encoding })
+    }
+
+    /// Write an event of the session.
+    pub fn write(&mut self, event: &RecordedEvent) -> Result<(), RecordingError> {
+        match self.encoding {
+            Encoding::JsonLines => {
+                serde_json::to_writer(&mut self.writer, event)?;
+                self.writer.write_all(b"\n")?;
+            }
+            Encoding::Binary => bincode::serialize_into(&mut self.writer, event)?,
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<(), RecordingError> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// The encoding of the session.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads a session, it is an iterator over the recorded events.
+///
+/// See the [module level documentation](crate::recording).
+pub struct SessionReader<R: BufRead> {
+    reader: R,
+    encoding: Encoding,
+    header: Header,
+    line: String,
+}
+
+impl SessionReader<BufReader<File>> {
+    /// Open a session file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RecordingError> {
+        SessionReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> SessionReader<R> {
+    /// Start reading a session, by detecting its encoding and reading its header.
+    pub fn new(mut reader: R) -> Result<Self, RecordingError> {
+        let is_binary = reader.fill_buf()?.starts_with(MAGIC);
+        if is_binary {
+            let mut prefix = [0u8; 11];
+            reader.read_exact(&mut prefix)?;
+            let version = u32::from_le_bytes([prefix[7], prefix[8], prefix[9], prefix[10]]);
+            if version > FORMAT_VERSION {
+                return Err(RecordingError::UnsupportedVersion(version));
+            }
+
+            let header = bincode::deserialize_from(&mut reader)?;
+            return Ok(SessionReader {
+                reader,
+                encoding: Encoding::Binary,
+                header,
+                line: String::new(),
+            });
+        }
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.trim_start().starts_with('{') {
+            return Err(RecordingError::UnknownFormat);
+        }
+        // The version is checked first, since the rest of the header might have changed.
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let version = serde_json::from_str::<Version>(&line)
+            .map_err(|_| RecordingError::UnknownFormat)?
+            .version;
+        if version > FORMAT_VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+
+        let header = serde_json::from_str(&line)?;
+        Ok(SessionReader {
+            reader,
+            encoding: Encoding::JsonLines,
+            header,
+            line,
+        })
+    }
+
+    /// The header of the session.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The encoding of the session.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    fn read_event(&mut self) -> Result<Option<RecordedEvent>, RecordingError> {
+        match self.encoding {
+            Encoding::JsonLines => loop {
+                self.line.clear();
+                if self.reader.read_line(&mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                if !self.line.trim().is_empty() {
+                    return Ok(Some(serde_json::from_str(&self.line)?));
+                }
+            },
+            Encoding::Binary => {
+                // A session ends between two events, anything else is a truncated event.
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(bincode::deserialize_from(&mut self.reader)?))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SessionReader<R> {
+    type Item = Result<RecordedEvent, RecordingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
@@ -0,0 +1,561 @@
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+use crate::error::{HookError, RecordingError};
+use crate::hook::clock::Clock;
+use crate::hook::event::{EventKind, EventMode, HookEvent, Modifiers};
+use crate::hook::state::KeyboardState;
+use crate::hook::Hook;
+use crate::hotkey::{Hotkey, Trigger};
+use crate::recording::{Header, RecordedEvent, SessionReader};
+use crate::system_properties::{self, ScreenData};
+
+/// The state of a [`Player`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerState {
+    /// The player was not started yet.
+    Idle,
+    /// The events are replayed.
+    Playing,
+    /// The replay is paused until it is resumed.
+    Paused,
+    /// All the events were replayed, or posting one of them failed.
+    Finished,
+    /// The replay was stopped with [`Player::stop`].
+    Stopped,
+    /// The replay was aborted with the abort hotkey, see [`Player::with_abort_hotkey`].
+    Aborted,
+}
+
+/// How the positions of the mouse events are adapted to the screens of the current system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Geometry {
+    /// Scale the positions from the area covered by the screens of the recording to the area covered
+    /// by the current screens. Positions are kept as they are if either is unknown.
+    Scale,
+    /// Keep the recorded positions as they are.
+    Unchanged,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry::Scale
+    }
+}
+
+struct Control {
+    state: PlayerState,
+    // The position in the session, in milliseconds, it only advances while playing.
+    clock: Clock,
+    looping: bool,
+    seeked: bool,
+    // The release events of the keys and buttons pressed by the replayed events.
+    held: Vec<HookEvent>,
+}
+
+impl Control {
+    fn track(&mut self, event: &HookEvent) {
+        let release = match &event.kind {
+            EventKind::KeyPressed(data) => HookEvent {
+                metadata: event.metadata.clone(),
+                kind: EventKind::KeyReleased(data.clone()),
+            },
+            EventKind::MousePressed(data) => HookEvent {
+                metadata: event.metadata.clone(),
+                kind: EventKind::MouseReleased(data.clone()),
+            },
+            EventKind::KeyReleased(_) | EventKind::MouseReleased(_) => {
+                self.held.retain(|held| !releases(held, event));
+                return;
+            }
+            _ => return,
+        };
+        if !self.held.iter().any(|held| releases(held, &release)) {
+            self.held.push(release);
+        }
+    }
+}
+
+/// Release the keys and buttons left held, so they are not stuck when jumping around the session.
+///
+/// The releases are posted without holding the lock, all of them are posted even if one fails,
+/// and the error of the first one that failed is returned.
+fn release_held(control: &mut MutexGuard<Control>) -> Result<(), HookError> {
+    let held = std::mem::take(&mut control.held);
+    MutexGuard::unlocked(control, || {
+        held.into_iter()
+            .map(|release| release.post())
+            .fold(Ok(()), Result::and)
+    })
+}
+
+/// Check if both events release the same key or button.
+fn releases(a: &HookEvent, b: &HookEvent) -> bool {
+    match (&a.kind, &b.kind) {
+        (EventKind::KeyReleased(a), EventKind::KeyReleased(b)) => a.keycode == b.keycode,
+        (EventKind::MouseReleased(a), EventKind::MouseReleased(b)) => a.button == b.button,
+        _ => false,
+    }
+}
+
+struct Shared {
+    control: Mutex<Control>,
+    cond: Condvar,
+}
+
+/// Replays a recorded session by posting its events, see the [module level documentation](crate::recording).
+///
+/// The events are posted with [`post_event`] on a thread of the player, at the offsets they were recorded
+/// at, scaled by the speed of the player. The replay can be paused, resumed and moved to another position
+/// at any time, and the keys and mouse buttons pressed by the replayed events are released whenever the
+/// replay stops or jumps, so that none of them is left stuck.
+///
+/// Only the events that make the operating system generate the others are replayed, the [`KeyTyped`] and
+/// [`MouseClicked`] events are skipped since they are generated again from the replayed presses.
+///
+/// # Example
+/// ```rust
+/// use std::time::{Duration, Instant};
+///
+/// use uiohook_rs::hook::event::Key;
+/// use uiohook_rs::recording::{Player, PlayerState, RecordedEvent};
+/// use uiohook_rs::testing::VirtualDevice;
+/// use uiohook_rs::HookEvent;
+///
+/// let device = VirtualDevice::new();
+/// device.install();
+///
+/// let (press, release) = HookEvent::keyboard(Key::A).pair().into();
+/// let events = vec![
+///     RecordedEvent { offset: 0, event: press },
+///     RecordedEvent { offset: 400, event: release },
+/// ];
+///
+/// // replay the session twice as fast as it was recorded.
+/// let mut player = Player::new(events).with_speed(2.0);
+/// let started = Instant::now();
+/// player.play();
+/// assert_eq!(player.wait().unwrap(), PlayerState::Finished);
+///
+/// assert!(started.elapsed() >= Duration::from_millis(200));
+/// assert_eq!(device.posted().len(), 2);
+/// ```
+///
+/// [`post_event`]: crate::hook::global::post_event
+/// [`KeyTyped`]: crate::hook::event::EventKind::KeyTyped
+/// [`MouseClicked`]: crate::hook::event::EventKind::MouseClicked
+pub struct Player {
+    events: Vec<RecordedEvent>,
+    header: Option<Header>,
+    geometry: Geometry,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Option<HookError>>>,
+    _abort: Option<Hook>,
+}
+
+impl Player {
+    /// Create a player for the events of a session, without a header the positions of the mouse events
+    /// are not adapted to the current screens.
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Player {
+            events,
+            header: None,
+            geometry: Geometry::default(),
+            shared: Arc::new(Shared {
+                control: Mutex::new(Control {
+                    state: PlayerState::Idle,
+                    clock: Clock::new(),
+                    looping: false,
+                    seeked: false,
+                    held: Vec::new(),
+                }),
+                cond: Condvar::new(),
+            }),
+            thread: None,
+            _abort: None,
+        }
+    }
+
+    /// Create a player for a session, reading all of its events.
+    pub fn from_session<R: BufRead>(session: SessionReader<R>) -> Result<Self, RecordingError> {
+        let header = session.header().clone();
+        let events = session.collect::<Result<Vec<_>, _>>()?;
+        let mut player = Player::new(events);
+        player.header = Some(header);
+        Ok(player)
+    }
+
+    /// Create a player for a session file, see [`SessionReader::open`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RecordingError> {
+        Player::from_session(SessionReader::open(path)?)
+    }
+
+    /// Set how many times faster than recorded the session is replayed, see [`set_speed`](Player::set_speed).
+    pub fn with_speed(self, speed: f64) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Set whether the replay starts over once all the events were replayed, until it is stopped.
+    pub fn with_loop(self, looping: bool) -> Self {
+        self.shared.control.lock().looping = looping;
+        self
+    }
+
+    /// Set how the positions of the mouse events are adapted to the current screens.
+    pub fn with_geometry(mut self, geometry: Geometry) -> Self {
+        self.geometry = geometry;
+        self
+    }
+
+    /// Abort the replay when `hotkey` is pressed.
+    ///
+    /// The hotkey is watched by a hook registered in the global session, which must be running for the
+    /// hotkey to be noticed. Synthetic events are ignored, so the replayed events cannot abort the replay.
+    pub fn with_abort_hotkey(mut self, hotkey: Hotkey) -> Self {
+        let shared = self.shared.clone();
+        let mut hook = Hook::new(move |event| {
+            let trigger = match &event.kind {
+                EventKind::KeyPressed(data) => Trigger::Key(data.keycode),
+                EventKind::MousePressed(data) => Trigger::Button(data.button),
+                _ => return,
+            };
+            if event.is_synthetic() || event.is_repeat() || trigger != hotkey.trigger() {
+                return;
+            }
+
+            let mut held = match KeyboardState::current() {
+                Some(keyboard) => keyboard.modifiers().keys(),
+                None => event.metadata.mask.keys(),
+            };
+            if let Trigger::Key(key) = trigger {
+                held.remove(Modifiers::from_key(key));
+            }
+            if hotkey.matches(held) {
+                let mut control = shared.control.lock();
+                if matches!(control.state, PlayerState::Playing | PlayerState::Paused) {
+                    control.state = PlayerState::Aborted;
+                    shared.cond.notify_all();
+                }
+            }
+        });
+        hook.register();
+        self._abort = Some(hook);
+        self
+    }
+
+    /// Start replaying the session, or resume a paused replay.
+    ///
+    /// A replay that already ended starts over from the beginning, while a replay that was not started
+    /// yet starts from the position it was moved to with [`seek`](Player::seek).
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::thread::sleep;
+    /// use std::time::Duration;
+    ///
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::recording::{Player, PlayerState, RecordedEvent};
+    /// use uiohook_rs::testing::VirtualDevice;
+    /// use uiohook_rs::{EventKind, HookEvent};
+    ///
+    /// let device = VirtualDevice::new();
+    /// device.install();
+    ///
+    /// let (press, release) = HookEvent::keyboard(Key::A).pair().into();
+    /// let events = vec![
+    ///     RecordedEvent { offset: 0, event: press },
+    ///     RecordedEvent { offset: 200, event: release },
+    /// ];
+    ///
+    /// let mut player = Player::new(events);
+    /// player.play();
+    /// while device.posted().is_empty() {
+    ///     sleep(Duration::from_millis(1));
+    /// }
+    ///
+    /// // stopping releases the key, and playing again starts over from the beginning.
+    /// player.stop();
+    /// player.play();
+    /// assert_eq!(player.wait().unwrap(), PlayerState::Finished);
+    ///
+    /// let posted = device.posted();
+    /// assert_eq!(posted.len(), 4);
+    /// assert!(matches!(posted[1].kind, EventKind::KeyReleased(_)));
+    /// assert!(matches!(posted[2].kind, EventKind::KeyPressed(_)));
+    /// ```
+    pub fn play(&mut self) {
+        let mut control = self.shared.control.lock();
+        match control.state {
+            PlayerState::Playing => return,
+            PlayerState::Paused => {
+                drop(control);
+                self.resume();
+                return;
+            }
+            _ => (),
+        }
+
+        // The thread of a replay that was just stopped might not have noticed it yet, it is joined
+        // before the state changes, otherwise it would carry on with the new replay.
+        if let Some(thread) = self.thread.take() {
+            drop(control);
+            let _ = thread.join();
+            control = self.shared.control.lock();
+        }
+        // The clock of a replay that was stopped is still running, it is paused while it is moved back
+        // to the start, otherwise the replay would start slightly after the events at the start.
+        control.clock.run(false);
+        if control.state != PlayerState::Idle {
+            control.clock.set(0.0);
+        }
+        control.state = PlayerState::Playing;
+        control.clock.run(true);
+        drop(control);
+
+        let events = self.prepare();
+        let shared = self.shared.clone();
+        self.thread = Some(thread::spawn(move || run(&shared, &events)));
+    }
+
+    /// Pause the replay, the keys and buttons held by the replayed events stay held.
+    pub fn pause(&self) {
+        let mut control = self.shared.control.lock();
+        if control.state == PlayerState::Playing {
+            control.state = PlayerState::Paused;
+            control.clock.run(false);
+            self.shared.cond.notify_all();
+        }
+    }
+
+    /// Resume a paused replay.
+    pub fn resume(&self) {
+        let mut control = self.shared.control.lock();
+        if control.state == PlayerState::Paused {
+            control.state = PlayerState::Playing;
+            control.clock.run(true);
+            self.shared.cond.notify_all();
+        }
+    }
+
+    /// Move the replay to `position` in the session, the events before it are skipped.
+    ///
+    /// The keys and buttons held by the replayed events are released first, so seeking backwards
+    /// replays their presses again.
+    pub fn seek(&self, position: Duration) {
+        let mut control = self.shared.control.lock();
+        control.clock.set(position.as_secs_f64() * 1000.0);
+        control.seeked = true;
+        self.shared.cond.notify_all();
+    }
+
+    /// Stop the replay, releasing the keys and buttons held by the replayed events.
+    pub fn stop(&self) {
+        let mut control = self.shared.control.lock();
+        if matches!(control.state, PlayerState::Playing | PlayerState::Paused) {
+            control.state = PlayerState::Stopped;
+            self.shared.cond.notify_all();
+        }
+    }
+
+    /// Block until the replay ends, and return how it ended.
+    ///
+    /// Returns an error if one of the events could not be posted, which ends the replay.
+    /// A replay that loops only ends once it is stopped or aborted.
+    pub fn wait(&mut self) -> Result<PlayerState, HookError> {
+        if let Some(thread) = self.thread.take() {
+            if let Some(err) = thread.join().expect("the player thread panicked") {
+                return Err(err);
+            }
+        }
+        Ok(self.state())
+    }
+
+    /// Set how many times faster than recorded the session is replayed, `2.0` replays it twice as fast.
+    ///
+    /// # Panics
+    /// Panics if the speed is not a positive number.
+    pub fn set_speed(&self, speed: f64) {
+        self.shared.control.lock().clock.set_speed(speed);
+        self.shared.cond.notify_all();
+    }
+
+    /// The state of the replay.
+    pub fn state(&self) -> PlayerState {
+        self.shared.control.lock().state
+    }
+
+    /// The position of the replay in the session.
+    pub fn position(&self) -> Duration {
+        let position = self.shared.control.lock().clock.now();
+        Duration::from_secs_f64(position.max(0.0) / 1000.0).min(self.duration())
+    }
+
+    /// The length of the session, which is the offset of its last event.
+    pub fn duration(&self) -> Duration {
+        let last = self.events.iter().map(|event| event.offset).max();
+        Duration::from_millis(last.unwrap_or(0))
+    }
+
+    /// The events to post, adapted to the current screens.
+    fn prepare(&self) -> Vec<RecordedEvent> {
+        let mapping = match (self.geometry, &self.header) {
+            (Geometry::Scale, Some(header)) => {
+                let current = system_properties::screen_info().unwrap_or_default();
+                Area::of(&header.screens).zip(Area::of(&current))
+            }
+            _ => None,
+        };
+
+        let mut events: Vec<RecordedEvent> = self
+            .events
+            .iter()
+            .filter(|recorded| {
+                !matches!(
+                    recorded.event.kind,
+                    EventKind::Enabled
+                        | EventKind::Disabled
+                        | EventKind::KeyTyped(_)
+                        | EventKind::MouseClicked(_)
+                )
+            })
+            .cloned()
+            .collect();
+        for recorded in &mut events {
+            let metadata = &mut recorded.event.metadata;
+            metadata.mode = EventMode::SYNTHETIC;
+            metadata.time = 0;
+            if let Some((from, to)) = &mapping {
+                match &mut recorded.event.kind {
+                    EventKind::MousePressed(data)
+                    | EventKind::MouseReleased(data)
+                    | EventKind::MouseMoved(data)
+                    | EventKind::MouseDragged(data) => {
+                        data.x = Area::map(data.x, from.x, to.x);
+                        data.y = Area::map(data.y, from.y, to.y);
+                    }
+                    EventKind::MouseWheel(data) => {
+                        data.x = Area::map(data.x, from.x, to.x);
+                        data.y = Area::map(data.y, from.y, to.y);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        // The events are replayed in the order of their offsets, a session edited by hand might not be.
+        events.sort_by_key(|recorded| recorded.offset);
+        events
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The area covered by screens, as the start and the length of each axis.
+struct Area {
+    x: (i32, i32),
+    y: (i32, i32),
+}
+
+impl Area {
+    fn of(screens: &[ScreenData]) -> Option<Area> {
+        let left = screens.iter().map(|s| i32::from(s.origin_x)).min()?;
+        let top = screens.iter().map(|s| i32::from(s.origin_y)).min()?;
+        let right = screens
+            .iter()
+            .map(|s| i32::from(s.origin_x) + i32::from(s.width))
+            .max()?;
+        let bottom = screens
+            .iter()
+            .map(|s| i32::from(s.origin_y) + i32::from(s.height))
+            .max()?;
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(Area {
+            x: (left, right - left),
+            y: (top, bottom - top),
+        })
+    }
+
+    fn map(value: i16, from: (i32, i32), to: (i32, i32)) -> i16 {
+        let scaled = to.0 + (i32::from(value) - from.0) * to.1 / from.1;
+        scaled.clamp(to.0, to.0 + to.1 - 1) as i16
+    }
+}
+
+/// The replay loop, returns the error of the event that could not be posted.
+fn run(shared: &Shared, events: &[RecordedEvent]) -> Option<HookError> {
+    // The events are looked up from the position the replay started at or was moved to,
+    // the running clock is already past it and would skip the events at that exact position.
+    let first_at = |position: f64| events.partition_point(|e| (e.offset as f64) < position);
+
+    let mut control = shared.control.lock();
+    let mut index = first_at(control.clock.position());
+    control.seeked = false;
+    let mut error = None;
+    loop {
+        match control.state {
+            PlayerState::Playing => (),
+            PlayerState::Paused => {
+                shared.cond.wait(&mut control);
+                continue;
+            }
+            _ => break,
+        }
+        if control.seeked {
+            control.seeked = false;
+            if let Err(err) = release_held(&mut control) {
+                control.state = PlayerState::Finished;
+                error = Some(err);
+                break;
+            }
+            index = first_at(control.clock.position());
+            continue;
+        }
+        if index >= events.len() {
+            if control.looping && !events.is_empty() {
+                if let Err(err) = release_held(&mut control) {
+                    control.state = PlayerState::Finished;
+                    error = Some(err);
+                    break;
+                }
+                control.clock.set(0.0);
+                index = 0;
+                continue;
+            }
+            control.state = PlayerState::Finished;
+            break;
+        }
+
+        let recorded = &events[index];
+        if let Some(wait) = control.clock.until(recorded.offset as f64) {
+            shared.cond.wait_for(&mut control, wait);
+            continue;
+        }
+
+        index += 1;
+        control.track(&recorded.event);
+        let event = recorded.event.clone();
+        if let Err(err) = MutexGuard::unlocked(&mut control, || event.post()) {
+            control.state = PlayerState::Finished;
+            error = Some(err);
+            break;
+        }
+    }
+
+    // The releases are posted whichever way the replay ended, the first error is the one reported.
+    let released = release_held(&mut control);
+    error.or(released.err())
+}
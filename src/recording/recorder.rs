@@ -0,0 +1,239 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::error::RecordingError;
+use crate::hook::dispatch::DispatchMode;
+use crate::hook::event::{now, EventKind, HookEvent};
+use crate::hook::session::HookSession;
+use crate::hook::Hook;
+use crate::recording::{Encoding, Header, RecordedEvent, SessionWriter};
+
+/// The state of a [`Recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecorderState {
+    /// The recorder was not started yet.
+    Idle,
+    /// The events are recorded.
+    Recording,
+    /// The events are ignored until the recorder is resumed.
+    Paused,
+    /// The recorder was stopped and the session is complete.
+    Stopped,
+}
+
+type Output = Box<dyn Write + Send>;
+
+enum Writer {
+    Pending(Output, Encoding),
+    Writing(SessionWriter<Output>),
+    Closed,
+}
+
+struct Inner {
+    writer: Writer,
+    paused: bool,
+    synthetic: bool,
+    // The time the recording started, and the total time it was paused, in milliseconds.
+    started_at: u128,
+    paused_at: u128,
+    paused_for: u128,
+    // The first error that occurred while recording an event, returned by `stop`.
+    error: Option<RecordingError>,
+}
+
+impl Inner {
+    fn record(&mut self, event: &HookEvent) {
+        // Control events cannot be replayed, and synthetic events were most likely replayed themselves.
+        if self.paused
+            || matches!(event.kind, EventKind::Enabled | EventKind::Disabled)
+            || (event.is_synthetic() && !self.synthetic)
+        {
+            return;
+        }
+        let writer = match &mut self.writer {
+            Writer::Writing(writer) => writer,
+            _ => return,
+        };
+
+        let time = match event.metadata.time {
+            0 => now(),
+            time => time,
+        };
+        let offset = time.saturating_sub(self.started_at + self.paused_for) as u64;
+        let recorded = RecordedEvent {
+            offset,
+            event: event.clone(),
+        };
+        if let Err(err) = writer.write(&recorded) {
+            // The session is broken from this point on, so we stop writing to it.
+            self.error = Some(err);
+            self.writer = Writer::Closed;
+        }
+    }
+}
+
+/// Records the events received by a session to a file, see the [module level documentation](crate::recording).
+///
+/// The recorder is a hook dispatched on a dedicated thread, so writing the session never delays the other
+/// hooks. The hook is unregistered when the recorder is dropped, though the session is only guaranteed to be
+/// complete once [`stop`](Recorder::stop) returns successfully.
+///
+/// Control events are never recorded, and synthetic events are only recorded if enabled with
+/// [`with_synthetic`](Recorder::with_synthetic), so that replaying a session while recording does not
+/// record the replayed events.
+///
+/// # Example
+/// ```rust
+/// use uiohook_rs::hook::event::Key;
+/// use uiohook_rs::recording::{Encoding, RecordedEvent, Recorder, SessionReader, FORMAT_VERSION};
+/// use uiohook_rs::testing::VirtualDevice;
+/// use uiohook_rs::{hook_start, EventKind, HookEvent};
+///
+/// let device = VirtualDevice::new();
+/// device.install();
+///
+/// let path = std::env::temp_dir().join("uiohook-recorder-example.jsonl");
+/// let recorder = Recorder::create(&path, Encoding::JsonLines).unwrap();
+/// recorder.start().unwrap();
+///
+/// let handle = hook_start().expect("oops hook already running");
+/// device.feed(HookEvent::keyboard(Key::A).press());
+/// device.feed(HookEvent::keyboard(Key::A).release());
+/// // synthetic events are not recorded by default.
+/// HookEvent::keyboard(Key::B).pair().post().unwrap();
+/// handle.stop().unwrap();
+/// recorder.stop().unwrap();
+///
+/// let reader = SessionReader::open(&path).unwrap();
+/// assert_eq!(reader.header().version, FORMAT_VERSION);
+/// let events: Vec<RecordedEvent> = reader.map(Result::unwrap).collect();
+/// assert_eq!(events.len(), 2);
+/// assert!(matches!(&events[0].event.kind, EventKind::KeyPressed(data) if data.keycode == Key::A));
+/// assert!(matches!(&events[1].event.kind, EventKind::KeyReleased(_)));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct Recorder {
+    inner: Arc<Mutex<Inner>>,
+    _hook: Hook,
+}
+
+impl Recorder {
+    /// Create a recorder that writes the session to `writer`, for the global session.
+    ///
+    /// The writer is not buffered, wrap files in a [`BufWriter`], or use [`create`](Recorder::create).
+    ///
+    /// [`BufWriter`]: std::io::BufWriter
+    pub fn new<W: Write + Send + 'static>(writer: W, encoding: Encoding) -> Self {
+        Self::for_session(HookSession::global(), writer, encoding)
+    }
+
+    /// Create a recorder that writes the session to `writer`, for the specified session.
+    pub fn for_session<W: Write + Send + 'static>(
+        session: &HookSession,
+        writer: W,
+        encoding: Encoding,
+    ) -> Self {
+        let inner = Arc::new(Mutex::new(Inner {
+            writer: Writer::Pending(Box::new(writer), encoding),
+            paused: false,
+            synthetic: false,
+            started_at: 0,
+            paused_at: 0,
+            paused_for: 0,
+            error: None,
+        }));
+        let hook_inner = inner.clone();
+        let mut hook = Hook::new(move |event| hook_inner.lock().record(event))
+            .with_dispatch(DispatchMode::Dedicated);
+        hook.register_in(session);
+
+        Recorder { inner, _hook: hook }
+    }
+
+    /// Create a recorder that writes the session to a new file at `path`, for the global session.
+    ///
+    /// The file is truncated if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P, encoding: Encoding) -> Result<Self, RecordingError> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file), encoding))
+    }
+
+    /// Set whether synthetic events are recorded, they are not recorded by default.
+    pub fn with_synthetic(self, synthetic: bool) -> Self {
+        self.inner.lock().synthetic = synthetic;
+        self
+    }
+
+    /// Start recording by writing the header of the session, or resume a paused recording.
+    ///
+    /// Returns an error if the header could not be written, or if the recorder was already stopped.
+    pub fn start(&self) -> Result<(), RecordingError> {
+        let mut inner = self.inner.lock();
+        match mem::replace(&mut inner.writer, Writer::Closed) {
+            Writer::Pending(output, encoding) => {
+                let header = Header::current();
+                inner.started_at = header.started_at;
+                inner.paused = false;
+                inner.writer = Writer::Writing(SessionWriter::new(output, encoding, &header)?);
+                Ok(())
+            }
+            Writer::Writing(writer) => {
+                inner.writer = Writer::Writing(writer);
+                drop(inner);
+                self.resume();
+                Ok(())
+            }
+            Writer::Closed => Err(RecordingError::Stopped),
+        }
+    }
+
+    /// Stop recording events until [`resume`](Recorder::resume) is called, the time the recorder is
+    /// paused is not included in the offsets of the events.
+    pub fn pause(&self) {
+        let mut inner = self.inner.lock();
+        if matches!(inner.writer, Writer::Writing(_)) && !inner.paused {
+            inner.paused = true;
+            inner.paused_at = now();
+        }
+    }
+
+    /// Resume a paused recording.
+    pub fn resume(&self) {
+        let mut inner = self.inner.lock();
+        if inner.paused {
+            inner.paused = false;
+            inner.paused_for += now().saturating_sub(inner.paused_at);
+        }
+    }
+
+    /// Stop recording, and flush the session.
+    ///
+    /// Returns the first error that occurred while recording, the recorder cannot be started again.
+    pub fn stop(&self) -> Result<(), RecordingError> {
+        let mut inner = self.inner.lock();
+        let flushed = match mem::replace(&mut inner.writer, Writer::Closed) {
+            Writer::Writing(mut writer) => writer.flush(),
+            _ => Ok(()),
+        };
+        match inner.error.take() {
+            Some(err) => Err(err),
+            None => flushed,
+        }
+    }
+
+    /// The current state of the recorder.
+    pub fn state(&self) -> RecorderState {
+        let inner = self.inner.lock();
+        match inner.writer {
+            Writer::Pending(..) => RecorderState::Idle,
+            Writer::Writing(_) if inner.paused => RecorderState::Paused,
+            Writer::Writing(_) => RecorderState::Recording,
+            Writer::Closed => RecorderState::Stopped,
+        }
+    }
+}
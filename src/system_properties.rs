@@ -1,8 +1,12 @@
 //! Utility methods for system properties that might affect how events are interpreted.
 
+use std::time::Duration;
+
 use ffi::screen_data;
 use uiohook_sys as ffi;
 
+use crate::error::{ErrorKind, HookError, Operation};
+
 crate::map_native! {
     /// Data describing a single monitor.
     ///
@@ -23,6 +27,10 @@ crate::map_native! {
     }
 }
 
+/// The rate at which a held key is repeated, in the unit of the platform, see [`key_repeat_interval`].
+///
+/// On Windows this is an index from 0, about 2.5 repeats per second, to 31, about 30 repeats per second.
+/// On the other platforms this is the interval between the repeats in milliseconds.
 pub fn auto_repeat_rate() -> Option<u64> {
     let rr: i64 = unsafe { ffi::hook_get_auto_repeat_rate() as i64 };
     if rr < 0 {
@@ -32,6 +40,10 @@ pub fn auto_repeat_rate() -> Option<u64> {
     }
 }
 
+/// The delay before a held key is repeated, in the unit of the platform, see [`key_repeat_delay`].
+///
+/// On Windows this is an index from 0, 250 milliseconds, to 3, one second.
+/// On the other platforms this is the delay in milliseconds.
 pub fn auto_repeat_delay() -> Option<u64> {
     let rd: i64 = unsafe { ffi::hook_get_auto_repeat_delay() as i64 };
     if rd < 0 {
@@ -41,6 +53,43 @@ pub fn auto_repeat_delay() -> Option<u64> {
     }
 }
 
+/// The delay before a held key is repeated, converted from [`auto_repeat_delay`].
+pub fn key_repeat_delay() -> Option<Duration> {
+    auto_repeat_delay().map(repeat_delay_from_native)
+}
+
+/// The interval between the repeats of a held key, converted from [`auto_repeat_rate`].
+///
+/// Returns `None` if the rate is unknown, or if keys are not repeated.
+pub fn key_repeat_interval() -> Option<Duration> {
+    auto_repeat_rate().and_then(repeat_interval_from_native)
+}
+
+#[cfg(windows)]
+fn repeat_delay_from_native(delay: u64) -> Duration {
+    // `SPI_GETKEYBOARDDELAY` goes from 0 to 3 in steps of 250 milliseconds.
+    Duration::from_millis((delay.min(3) + 1) * 250)
+}
+
+#[cfg(not(windows))]
+fn repeat_delay_from_native(delay: u64) -> Duration {
+    Duration::from_millis(delay)
+}
+
+#[cfg(windows)]
+fn repeat_interval_from_native(rate: u64) -> Option<Duration> {
+    // `SPI_GETKEYBOARDSPEED` goes linearly from about 2.5 repeats per second at 0 to about 30 at 31.
+    let per_second = 2.5 + rate.min(31) as f64 * 27.5 / 31.0;
+    Some(Duration::from_secs_f64(1.0 / per_second))
+}
+
+#[cfg(not(windows))]
+fn repeat_interval_from_native(rate: u64) -> Option<Duration> {
+    Some(rate)
+        .filter(|rate| *rate > 0)
+        .map(Duration::from_millis)
+}
+
 pub fn pointer_acceleration_multiplier() -> Option<u64> {
     let am: i64 = unsafe { ffi::hook_get_pointer_acceleration_multiplier() as i64 };
     if am < 0 {
@@ -77,11 +126,70 @@ pub fn multi_click_time() -> Option<u64> {
     }
 }
 
-pub fn screen_info() -> Vec<ScreenData> {
+/// A snapshot of the system properties, see [`SystemProperties::current`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemProperties {
+    /// See [`auto_repeat_rate`].
+    pub auto_repeat_rate: Option<u64>,
+    /// See [`auto_repeat_delay`].
+    pub auto_repeat_delay: Option<u64>,
+    /// See [`pointer_acceleration_multiplier`].
+    pub pointer_acceleration_multiplier: Option<u64>,
+    /// See [`pointer_acceleration_threshold`].
+    pub pointer_acceleration_threshold: Option<u64>,
+    /// See [`pointer_sensitivity`].
+    pub pointer_sensitivity: Option<u64>,
+    /// See [`multi_click_time`].
+    pub multi_click_time: Option<u64>,
+}
+
+impl SystemProperties {
+    /// Query all the system properties.
+    pub fn current() -> Self {
+        SystemProperties {
+            auto_repeat_rate: auto_repeat_rate(),
+            auto_repeat_delay: auto_repeat_delay(),
+            pointer_acceleration_multiplier: pointer_acceleration_multiplier(),
+            pointer_acceleration_threshold: pointer_acceleration_threshold(),
+            pointer_sensitivity: pointer_sensitivity(),
+            multi_click_time: multi_click_time(),
+        }
+    }
+}
+
+/// Query the layout of the connected screens.
+///
+/// Returns an error of kind [`ErrorKind::Other`] with the [`Operation::ScreenQuery`] context
+/// if the screens could not be queried.
+pub fn screen_info() -> Result<Vec<ScreenData>, HookError> {
     let native_vec = unsafe {
         let mut count = 1u8;
         let screens = ffi::hook_create_screen_info(&mut count);
+        if screens.is_null() {
+            return Err(HookError::new(
+                ErrorKind::Other,
+                Operation::ScreenQuery,
+                "Failed to retrieve the screen information.",
+            ));
+        }
         Vec::from_raw_parts(screens, count as usize, count as usize)
     };
-    native_vec.iter().map(ScreenData::from).collect()
+    Ok(native_vec.iter().map(ScreenData::from).collect())
+}
+
+/// Query the current position of the cursor from the display server.
+///
+/// This does not require the hook to be running, which makes it useful to find out where the cursor is
+/// before any mouse event was received, see [`MouseState::position`].
+/// Returns `None` if the position could not be retrieved.
+///
+/// [`MouseState::position`]: crate::hook::state::MouseState::position
+pub fn cursor_position() -> Option<(i16, i16)> {
+    let (mut x, mut y) = (0i16, 0i16);
+    if unsafe { ffi::hook_get_cursor_position(&mut x, &mut y) } {
+        Some((x, y))
+    } else {
+        None
+    }
 }
@@ -0,0 +1,306 @@
+//! Utilities for running hooks without a display server.
+//!
+//! The [`VirtualDevice`] is a [`Backend`] that never talks to the operating system, instead it feeds
+//! scripted events into the hook and captures every posted event into a log that can be inspected later.
+//! This makes it possible to drive the whole dispatch pipeline, from the control thread to the
+//! registered hooks, in environments that have no display, such as CI.
+//!
+//! # Example
+//! ```rust
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::hook::global::register_hook;
+//! use uiohook_rs::testing::VirtualDevice;
+//! use uiohook_rs::{hook_start, EventKind, HookEvent};
+//!
+//! static PRESSED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! // The scripted events will be fed into the hook right after it is enabled.
+//! let device = VirtualDevice::new().with_script(vec![
+//!     HookEvent::keyboard(Key::A).press(),
+//!     HookEvent::keyboard(Key::B).press(),
+//! ]);
+//! assert!(device.install());
+//!
+//! register_hook(|event| {
+//!     if let EventKind::KeyPressed(_) = event.kind {
+//!         PRESSED.fetch_add(1, Ordering::SeqCst);
+//!     }
+//! });
+//!
+//! let handle = hook_start().expect("oops hook already running");
+//! HookEvent::keyboard(Key::Escape).pair().post().unwrap();
+//! handle.stop().unwrap();
+//!
+//! // all the scripted events are dispatched before the hook is disabled.
+//! assert_eq!(PRESSED.load(Ordering::SeqCst), 2);
+//! // the posted events were captured instead of being sent to the OS.
+//! assert_eq!(device.posted().len(), 2);
+//! ```
+//!
+//! [`Backend`]: crate::hook::backend::Backend
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::HookError;
+use crate::hook::backend::{Backend, DispatchProc};
+use crate::hook::event::{now, EventKind, EventMetaData, EventMode, HookEvent};
+use crate::hook::global::set_backend;
+
+#[derive(Default)]
+struct DeviceState {
+    started: bool,
+    running: bool,
+    stop_requested: bool,
+    failure: Option<HookError>,
+    script: VecDeque<HookEvent>,
+}
+
+#[derive(Default)]
+struct DeviceInner {
+    dispatch: Mutex<Option<DispatchProc>>,
+    state: (Mutex<DeviceState>, Condvar),
+    posted: Mutex<Vec<HookEvent>>,
+    loopback: bool,
+}
+
+/// An in-memory input device implementing [`Backend`].
+///
+/// The device is cheap to clone, all clones share the same script and posted events log,
+/// so a clone can be installed as the backend while the original is used to feed events
+/// and inspect the log.
+///
+/// When started, the device dispatches an [`Enabled`] event, followed by all the scripted events
+/// in order. After that, every event passed to [`feed`] is dispatched immediately, until the device
+/// is stopped at which point a [`Disabled`] event is dispatched.
+///
+/// [`Backend`]: crate::hook::backend::Backend
+/// [`Enabled`]: crate::hook::event::EventKind::Enabled
+/// [`Disabled`]: crate::hook::event::EventKind::Disabled
+/// [`feed`]: VirtualDevice::feed
+#[derive(Clone, Default)]
+pub struct VirtualDevice {
+    inner: Arc<DeviceInner>,
+}
+
+impl VirtualDevice {
+    /// Create a device with no scripted events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add events to the script, the events will be dispatched in order right after the
+    /// device is started. Like events passed to [`feed`], scripted events are not marked as synthetic.
+    ///
+    /// [`feed`]: VirtualDevice::feed
+    pub fn with_script<I: IntoIterator<Item = HookEvent>>(self, events: I) -> Self {
+        for event in events {
+            self.feed(event);
+        }
+        self
+    }
+
+    /// Make the device echo every posted event back into the hook, simulating the way
+    /// the operating system dispatches posted events. Echoed events are marked as synthetic.
+    ///
+    /// Note that this method must be called before the device is cloned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// use uiohook_rs::hook::event::Key;
+    /// use uiohook_rs::hook::global::register_hook;
+    /// use uiohook_rs::testing::VirtualDevice;
+    /// use uiohook_rs::{hook_start, HookEvent};
+    ///
+    /// static SYNTHETIC: AtomicBool = AtomicBool::new(false);
+    ///
+    /// VirtualDevice::new().with_loopback().install();
+    /// register_hook(|event| {
+    ///     if event.is_synthetic() {
+    ///         SYNTHETIC.store(true, Ordering::SeqCst);
+    ///     }
+    /// });
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// HookEvent::keyboard(Key::A).press().post().unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// assert!(SYNTHETIC.load(Ordering::SeqCst));
+    /// ```
+    pub fn with_loopback(mut self) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("loopback must be enabled before the device is cloned")
+            .loopback = true;
+        self
+    }
+
+    /// Install a clone of this device as the backend of the hook, see [`set_backend`].
+    ///
+    /// Returns `false` if the hook is running and the backend could not be replaced.
+    ///
+    /// [`set_backend`]: crate::hook::global::set_backend
+    pub fn install(&self) -> bool {
+        set_backend(self.clone())
+    }
+
+    /// Feed an event into the hook as if it was generated by the user.
+    ///
+    /// If the device is running the event is dispatched immediately, otherwise it is appended
+    /// to the script and will be dispatched when the device is started.
+    /// If the event has no timestamp, it will be stamped with the time it is dispatched.
+    ///
+    /// Note that events created with the [`HookEvent`] builders are marked as synthetic, since
+    /// the device feeds events as if they were created by the user, the synthetic flag is removed.
+    pub fn feed(&self, mut event: HookEvent) {
+        event.metadata.mode.remove(EventMode::SYNTHETIC);
+
+        {
+            let mut state = self.inner.state.0.lock();
+            if !state.running {
+                state.script.push_back(event);
+                return;
+            }
+        }
+
+        self.dispatch(event);
+    }
+
+    /// Simulate a failure of the native hook, for example the connection to the display server being lost.
+    ///
+    /// If the device is running it stops without being asked to, and [`Backend::start`] returns `error`.
+    /// Otherwise the error is returned by the next call to [`Backend::start`], right after it is enabled.
+    ///
+    /// [`Backend::start`]: crate::hook::backend::Backend::start
+    pub fn fail(&self, error: HookError) {
+        let (ref lock, ref cond) = self.inner.state;
+        let mut state = lock.lock();
+        state.failure = Some(error);
+        state.stop_requested = true;
+        cond.notify_all();
+    }
+
+    /// Check if the device was started and not yet stopped.
+    pub fn is_running(&self) -> bool {
+        self.inner.state.0.lock().running
+    }
+
+    /// Get a copy of all the events posted through this device, in the order they were posted.
+    pub fn posted(&self) -> Vec<HookEvent> {
+        self.inner.posted.lock().clone()
+    }
+
+    /// Take all the events posted through this device, clearing the log.
+    pub fn take_posted(&self) -> Vec<HookEvent> {
+        mem::take(&mut *self.inner.posted.lock())
+    }
+
+    fn dispatch(&self, mut event: HookEvent) {
+        if event.metadata.time == 0 {
+            event.metadata.time = now();
+        }
+
+        let dispatch = self.inner.dispatch.lock().clone();
+        if let Some(dispatch) = dispatch {
+            dispatch(&mut event);
+        }
+    }
+
+    fn dispatch_control(&self, kind: EventKind) {
+        self.dispatch(HookEvent {
+            metadata: EventMetaData::default(),
+            kind,
+        });
+    }
+}
+
+impl Backend for VirtualDevice {
+    fn start(&self) -> Result<(), HookError> {
+        {
+            let mut state = self.inner.state.0.lock();
+            // a failure that was set while the device was stopped is reported by this run.
+            state.stop_requested = state.failure.is_some();
+            state.started = true;
+        }
+        self.dispatch_control(EventKind::Enabled);
+
+        // We drain the script one event at a time without holding the lock while dispatching,
+        // and only mark the device as running once the script is empty, this way events passed
+        // to `feed` while the script is dispatched are appended to the script and keep their order.
+        loop {
+            let next = {
+                let mut state = self.inner.state.0.lock();
+                match state.script.pop_front() {
+                    Some(event) => event,
+                    None => {
+                        state.running = true;
+                        break;
+                    }
+                }
+            };
+            self.dispatch(next);
+        }
+
+        let failure = {
+            let (ref lock, ref cond) = self.inner.state;
+            let mut state = lock.lock();
+            while !state.stop_requested {
+                cond.wait(&mut state);
+            }
+            state.running = false;
+            state.started = false;
+            state.failure.take()
+        };
+
+        // Like libuiohook, a failing device does not dispatch the `Disabled` event.
+        match failure {
+            Some(error) => Err(error),
+            None => {
+                self.dispatch_control(EventKind::Disabled);
+                Ok(())
+            }
+        }
+    }
+
+    fn stop(&self) -> Result<(), HookError> {
+        let (ref lock, ref cond) = self.inner.state;
+        lock.lock().stop_requested = true;
+        cond.notify_all();
+        Ok(())
+    }
+
+    fn post(&self, mut event: HookEvent) {
+        self.inner.posted.lock().push(event.clone());
+
+        if !self.inner.loopback {
+            return;
+        }
+
+        event.metadata.mode.insert(EventMode::SYNTHETIC);
+        event.metadata.time = 0;
+        {
+            let mut state = self.inner.state.0.lock();
+            if !state.started {
+                return;
+            }
+            // Events posted while the scripted events are dispatched are queued after them,
+            // like the events passed to `feed`.
+            if !state.running {
+                state.script.push_back(event);
+                return;
+            }
+        }
+
+        self.dispatch(event);
+    }
+
+    fn set_dispatch_proc(&self, dispatch: Option<DispatchProc>) {
+        *self.inner.dispatch.lock() = dispatch;
+    }
+}
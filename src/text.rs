@@ -0,0 +1,260 @@
+//! Reconstruct the text typed by the user.
+//!
+//! The operating system reports every typed character as a [`KeyTyped`] event holding a single UTF-16
+//! code unit in [`KeyboardEvent::keychar`], so characters outside the basic multilingual plane, like most
+//! emoji, arrive as two events holding a surrogate pair. A [`TextInput`] joins these events back into text,
+//! and follows the editing keys pressed along the way:
+//!
+//! *   [`Backspace`] and [`Delete`] remove the character before or after the caret.
+//! *   [`Left`], [`Right`], [`Home`] and [`End`] move the caret within the current line.
+//! *   Typing a line break completes the current line.
+//!
+//! The caret is only tracked within the current line, edits never reach back into completed lines.
+//! Mouse presses and vertical navigation move the caret to a place that cannot be known from the events,
+//! so they discard the current line, as does restarting the session.
+//!
+//! Words are separated by whitespace, and listeners registered with [`TextInput::on_word`] and
+//! [`TextInput::on_line`] are called whenever a word or a line is completed.
+//!
+//! # Example
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//!
+//! use uiohook_rs::hook::event::Key;
+//! use uiohook_rs::text::TextInput;
+//! use uiohook_rs::HookEvent;
+//!
+//! let words = Arc::new(Mutex::new(Vec::new()));
+//! let listener_words = words.clone();
+//! let mut input = TextInput::new().on_word(move |word| {
+//!     listener_words.lock().unwrap().push(word.to_string());
+//! });
+//!
+//! for event in HookEvent::typed("helo") {
+//!     input.feed(&event);
+//! }
+//! // fix the typo before moving on to the next word.
+//! input.feed(&HookEvent::keyboard(Key::Left).press());
+//! input.feed(&HookEvent::keyboard(Key::Left).press());
+//! for event in HookEvent::typed("l") {
+//!     input.feed(&event);
+//! }
+//! input.feed(&HookEvent::keyboard(Key::End).press());
+//! for event in HookEvent::typed(" 🦀") {
+//!     input.feed(&event);
+//! }
+//!
+//! assert_eq!(input.text(), "hello 🦀");
+//! assert_eq!(input.word(), "🦀");
+//! assert_eq!(*words.lock().unwrap(), vec!["hello"]);
+//! ```
+//!
+//! To follow the text typed while the hook is running, turn the [`TextInput`] into a hook
+//! with [`TextInput::into_hook`].
+//!
+//! [`KeyTyped`]: crate::hook::event::EventKind::KeyTyped
+//! [`KeyboardEvent::keychar`]: crate::hook::event::KeyboardEvent::keychar
+//! [`Backspace`]: crate::hook::event::Key::Backspace
+//! [`Delete`]: crate::hook::event::Key::Delete
+//! [`Left`]: crate::hook::event::Key::Left
+//! [`Right`]: crate::hook::event::Key::Right
+//! [`Home`]: crate::hook::event::Key::Home
+//! [`End`]: crate::hook::event::Key::End
+
+use std::char::REPLACEMENT_CHARACTER;
+
+use parking_lot::Mutex;
+
+use crate::hook::event::{EventKind, HookEvent, Key};
+use crate::hook::Hook;
+
+type TextListener = Box<dyn FnMut(&str) + Send>;
+
+/// Joins typed characters into text, see the [module level documentation](crate::text).
+#[derive(Default)]
+pub struct TextInput {
+    // The completed lines, each followed by a line break.
+    text: String,
+    line: Vec<char>,
+    caret: usize,
+    // The first half of a surrogate pair, waiting for the second half.
+    high_surrogate: Option<u16>,
+    word_listeners: Vec<TextListener>,
+    line_listeners: Vec<TextListener>,
+}
+
+impl TextInput {
+    /// Create an empty text input.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a listener that is called with every completed word.
+    ///
+    /// A word is completed when whitespace is typed right after it, the word is the run of
+    /// non whitespace characters right before the caret at that time.
+    pub fn on_word<F: FnMut(&str) + Send + 'static>(mut self, listener: F) -> Self {
+        self.word_listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Add a listener that is called with every completed line, without the line break.
+    pub fn on_line<F: FnMut(&str) + Send + 'static>(mut self, listener: F) -> Self {
+        self.line_listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Update the text from an event, events that do not affect the text are ignored.
+    pub fn feed(&mut self, event: &HookEvent) {
+        match &event.kind {
+            EventKind::KeyTyped(data) => self.push_unit(data.keychar),
+            EventKind::KeyPressed(data) => match data.keycode {
+                Key::Backspace if self.caret > 0 => {
+                    self.caret -= 1;
+                    self.line.remove(self.caret);
+                }
+                Key::Delete if self.caret < self.line.len() => {
+                    self.line.remove(self.caret);
+                }
+                Key::Left => self.caret = self.caret.saturating_sub(1),
+                Key::Right => self.caret = (self.caret + 1).min(self.line.len()),
+                Key::Home => self.caret = 0,
+                Key::End => self.caret = self.line.len(),
+                Key::Up | Key::Down | Key::PageUp | Key::PageDown => self.discard_line(),
+                _ => (),
+            },
+            EventKind::MousePressed(_) | EventKind::Enabled | EventKind::Disabled => {
+                self.discard_line()
+            }
+            _ => (),
+        }
+    }
+
+    /// All the text typed so far, including the current line.
+    pub fn text(&self) -> String {
+        let mut text = self.text.clone();
+        text.extend(&self.line);
+        text
+    }
+
+    /// The current line.
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// The word right before the caret, which will be passed to the word listeners once it is completed.
+    pub fn word(&self) -> String {
+        self.line[self.word_start()..self.caret].iter().collect()
+    }
+
+    /// Forget all the text typed so far.
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.discard_line();
+    }
+
+    /// Turn the text input into a hook that feeds it every event.
+    ///
+    /// # Example
+    /// ```rust
+    /// # uiohook_rs::testing::VirtualDevice::new().with_loopback().install();
+    /// use std::sync::mpsc::channel;
+    /// use std::sync::Mutex;
+    ///
+    /// use uiohook_rs::hook::event::{EventIterator, Key};
+    /// use uiohook_rs::text::TextInput;
+    /// use uiohook_rs::{hook_start, HookEvent};
+    ///
+    /// let (sender, lines) = channel();
+    /// let sender = Mutex::new(sender);
+    /// let mut hook = TextInput::new()
+    ///     .on_line(move |line| sender.lock().unwrap().send(line.to_string()).unwrap())
+    ///     .into_hook();
+    /// hook.register();
+    ///
+    /// let handle = hook_start().expect("oops hook already running");
+    /// HookEvent::typed("tset").into_iter().post().unwrap();
+    /// for _ in 0..3 {
+    ///     HookEvent::keyboard(Key::Backspace).pair().post().unwrap();
+    /// }
+    /// HookEvent::typed("est 😀\n").into_iter().post().unwrap();
+    /// handle.stop().unwrap();
+    ///
+    /// assert_eq!(lines.recv().unwrap(), "test 😀");
+    /// ```
+    pub fn into_hook(self) -> Hook {
+        let input = Mutex::new(self);
+        Hook::new(move |event| input.lock().feed(event))
+    }
+
+    fn push_unit(&mut self, unit: u16) {
+        if let Some(high) = self.high_surrogate.take() {
+            if (0xDC00..=0xDFFF).contains(&unit) {
+                let c = 0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(unit) - 0xDC00);
+                self.push_char(char::from_u32(c).unwrap_or(REPLACEMENT_CHARACTER));
+                return;
+            }
+            // The first half was never completed.
+            self.push_char(REPLACEMENT_CHARACTER);
+        }
+
+        match unit {
+            0xD800..=0xDBFF => self.high_surrogate = Some(unit),
+            0xDC00..=0xDFFF => self.push_char(REPLACEMENT_CHARACTER),
+            _ => self.push_char(char::from_u32(u32::from(unit)).unwrap_or(REPLACEMENT_CHARACTER)),
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        match c {
+            '\r' | '\n' => self.complete_line(),
+            // Tabs are text, other control characters are produced by the editing keys,
+            // which are handled through the key press events.
+            '\t' => self.insert(c),
+            c if c.is_control() => (),
+            c => self.insert(c),
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        if c.is_whitespace() {
+            self.complete_word();
+        }
+        self.line.insert(self.caret, c);
+        self.caret += 1;
+    }
+
+    fn word_start(&self) -> usize {
+        self.line[..self.caret]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |index| index + 1)
+    }
+
+    fn complete_word(&mut self) {
+        let word = self.word();
+        if !word.is_empty() {
+            for listener in &mut self.word_listeners {
+                listener(&word);
+            }
+        }
+    }
+
+    fn complete_line(&mut self) {
+        self.complete_word();
+
+        let line = self.line();
+        for listener in &mut self.line_listeners {
+            listener(&line);
+        }
+        self.text.push_str(&line);
+        self.text.push('\n');
+        self.discard_line();
+    }
+
+    fn discard_line(&mut self) {
+        self.line.clear();
+        self.caret = 0;
+        self.high_surrogate = None;
+    }
+}
@@ -15,6 +15,12 @@ fn main() {
         uihook_dst.join("lib").display()
     );
     println!("cargo:rustc-link-lib=user32");
+    // The cursor position query in the wrapper talks to the display server directly.
+    match env::var("CARGO_CFG_TARGET_OS").as_deref() {
+        Ok("linux") => println!("cargo:rustc-link-lib=X11"),
+        Ok("macos") => println!("cargo:rustc-link-lib=framework=ApplicationServices"),
+        _ => (),
+    }
     println!("cargo:rustc-link-lib=static=uiohook");
     println!("cargo:rustc-link-lib=static=wrapper");
     println!("cargo:include={}", uihook_dst.join("include").display());
@@ -1,6 +1,20 @@
 #include "wrapper.h"
 #include "stdio.h"
 
+#if defined(_WIN32)
+#include <windows.h>
+#elif defined(__APPLE__) && defined(__MACH__)
+#include <ApplicationServices/ApplicationServices.h>
+#else
+#include <pthread.h>
+#include <X11/Xlib.h>
+
+// The connection used to query the cursor position, it is opened by the first query and kept open.
+// Xlib connections are not thread safe, so the queries are serialized.
+static Display *cursor_display = NULL;
+static pthread_mutex_t cursor_display_mutex = PTHREAD_MUTEX_INITIALIZER;
+#endif
+
 #define LOGGER_BUFFER_SIZE 4096
 
 static bool default_rusty_logger(log_level level, const char *message) {
@@ -35,3 +49,52 @@ void hook_set_rusty_logger(rusty_logger_t logger) {
     }
 }
 
+// libuiohook does not expose the position of the cursor, so we ask the display server directly.
+// On X11 this uses a connection of its own, as the one of libuiohook is private to the library.
+bool hook_get_cursor_position(int16_t *x, int16_t *y) {
+#if defined(_WIN32)
+    POINT point;
+    if(!GetCursorPos(&point)) {
+        return false;
+    }
+
+    *x = (int16_t) point.x;
+    *y = (int16_t) point.y;
+    return true;
+#elif defined(__APPLE__) && defined(__MACH__)
+    CGEventRef event = CGEventCreate(NULL);
+    if(event == NULL) {
+        return false;
+    }
+
+    CGPoint point = CGEventGetLocation(event);
+    CFRelease(event);
+
+    *x = (int16_t) point.x;
+    *y = (int16_t) point.y;
+    return true;
+#else
+    Window root, child;
+    int root_x, root_y, win_x, win_y;
+    unsigned int mask;
+    Bool found = False;
+
+    pthread_mutex_lock(&cursor_display_mutex);
+    if(cursor_display == NULL) {
+        cursor_display = XOpenDisplay(NULL);
+    }
+    if(cursor_display != NULL) {
+        found = XQueryPointer(cursor_display, DefaultRootWindow(cursor_display), &root, &child,
+                &root_x, &root_y, &win_x, &win_y, &mask);
+    }
+    pthread_mutex_unlock(&cursor_display_mutex);
+
+    if(!found) {
+        return false;
+    }
+
+    *x = (int16_t) root_x;
+    *y = (int16_t) root_y;
+    return true;
+#endif
+}
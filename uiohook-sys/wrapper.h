@@ -4,4 +4,7 @@ typedef bool (*rusty_logger_t)(log_level, const char *);
 
 extern rusty_logger_t rusty_logger;
 
-void hook_set_rusty_logger(rusty_logger_t logger);
\ No newline at end of file
+void hook_set_rusty_logger(rusty_logger_t logger);
+
+// Query the current position of the cursor, returns false if the position could not be retrieved.
+bool hook_get_cursor_position(int16_t *x, int16_t *y);